@@ -1,15 +1,79 @@
 use lib::client::Client;
+use lib::logging::{LogLevel, LoggingMessageParams};
+use lib::types::{ProgressParams, ResourceContents};
 use serde_json::json;
+use std::collections::HashMap;
 use std::io::{self, Write};
+use std::process::Command;
+use std::time::Duration;
 
-fn main() -> Result<(), Box<dyn std::error::Error>> {
-    // Create a new client instance by spawning the server process and performing initialization.
-    let mut client = Client::new("target/debug/hello")?;
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    // The server command and its arguments, or an HTTP endpoint URL, may be given on the command line
+    // (`--sse <url>` connects to a server speaking the legacy HTTP+SSE transport)
+    let mut args = std::env::args().skip(1).peekable();
+    let legacy_sse = args.next_if_eq("--sse").is_some();
+    let target = args.next().unwrap_or_else(|| "target/debug/hello".to_string());
+
+    // Create a new client instance by connecting to the server and performing initialization.
+    let mut client = if legacy_sse {
+        Client::connect_sse(&target).await?
+    } else if target.starts_with("http://") || target.starts_with("https://") {
+        Client::connect_http(&target).await?
+    } else {
+        let mut command = Command::new(target);
+        command.args(args);
+        Client::from_command(command).await?
+    };
+
+    if let Some(info) = client.server_info() {
+        let version = client.protocol_version().unwrap_or_default();
+        println!("Connected to {} {} (protocol {})", info.name, info.version, version);
+    }
+
+    client.on_notification("notifications/message", |params| {
+        let message = params.and_then(|p| serde_json::from_value::<LoggingMessageParams>(p).ok());
+        let Some(message) = message else {
+            return;
+        };
+        let data = match message.data {
+            serde_json::Value::String(text) => text,
+            data => data.to_string(),
+        };
+        let logger = message.logger.map(|name| format!(" {}", name)).unwrap_or_default();
+        println!("[server {:?}{}] {}", message.level, logger, data);
+    });
+    client.on_notification("notifications/tools/list_changed", |_| {
+        println!("The server's tools changed; use /list to see them");
+    });
+    // The client advertises the roots capability, so offer the working directory
+    client.on_request("roots/list", |_| {
+        let cwd = std::env::current_dir().unwrap_or_default();
+        Ok(json!({
+            "roots": [{ "uri": format!("file://{}", cwd.display()), "name": "cwd" }]
+        }))
+    });
+
+    let mut updates = client.resource_updates();
+    tokio::spawn(async move {
+        while let Some(uri) = updates.recv().await {
+            println!("Resource updated: {}", uri);
+        }
+    });
 
     println!("Client initialized. Available commands:");
     println!("  /list - List available tools");
     println!("  /greet <name> - Call the greet tool with a name");
     println!("  /time - Call the get_time tool");
+    println!("  /sleep <seconds> - Call the sleep tool, showing its progress");
+    println!("  /timeout <seconds|off> - Cancel requests that take longer than this");
+    println!("  /resources - List available resources and resource templates");
+    println!("  /read <uri> - Read a resource");
+    println!("  /prompts - List available prompts");
+    println!("  /prompt <name> [<arg>=<value> ...] - Render a prompt");
+    println!("  /loglevel <level> - Receive server log messages at this level and above");
+    println!("  /subscribe <uri> - Get notified when a resource changes");
+    println!("  /unsubscribe <uri> - Stop notifications for a resource");
     println!("  /quit - Exit the client");
 
     let stdin = io::stdin();
@@ -31,9 +95,9 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         }
 
         let parts: Vec<&str> = command.split_whitespace().collect();
-        match parts.get(0) {
+        match parts.first() {
             Some(&"/list") => {
-                match client.list_tools(None) {
+                match client.list_tools(None).await {
                     Ok(tools_result) => {
                         println!("Available tools:");
                         for tool in tools_result.tools {
@@ -50,14 +114,14 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
             Some(&"/greet") => {
                 if let Some(name) = parts.get(1) {
                     let arguments = json!({ "name": name });
-                    match client.call_tool("greet".to_string(), arguments) {
+                    match client.call_tool("greet".to_string(), arguments).await {
                         Ok(result) => {
                             println!("Tool result (error: {}):", result.is_error);
                             for content in result.content {
-                                if content.content_type == "text" {
-                                    if let Some(text) = content.content.get("text").and_then(|v| v.as_str()) {
-                                        println!("  {}", text);
-                                    }
+                                if content.content_type == "text"
+                                    && let Some(text) = content.content.get("text").and_then(|v| v.as_str())
+                                {
+                                    println!("  {}", text);
                                 }
                             }
                         }
@@ -71,14 +135,14 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
             }
             Some(&"/time") => {
                 let arguments = json!({});
-                match client.call_tool("get_time".to_string(), arguments) {
+                match client.call_tool("get_time".to_string(), arguments).await {
                     Ok(result) => {
                         println!("Tool result (error: {}):", result.is_error);
                         for content in result.content {
-                            if content.content_type == "text" {
-                                if let Some(text) = content.content.get("text").and_then(|v| v.as_str()) {
-                                    println!("  {}", text);
-                                }
+                            if content.content_type == "text"
+                                && let Some(text) = content.content.get("text").and_then(|v| v.as_str())
+                            {
+                                println!("  {}", text);
                             }
                         }
                     }
@@ -87,16 +151,172 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                     }
                 }
             }
+            Some(&"/sleep") => {
+                if let Some(seconds) = parts.get(1).and_then(|s| s.parse::<f64>().ok()) {
+                    let arguments = json!({ "seconds": seconds });
+                    let on_progress = |progress: ProgressParams| {
+                        let total = progress.total.map(|total| format!("/{}", total)).unwrap_or_default();
+                        let message = progress.message.map(|m| format!(" {}", m)).unwrap_or_default();
+                        println!("  [progress {}{}]{}", progress.progress, total, message);
+                    };
+                    match client.call_tool_with_progress("sleep".to_string(), arguments, on_progress).await {
+                        Ok(result) => {
+                            println!("Tool result (error: {}):", result.is_error);
+                            for content in result.content {
+                                if content.content_type == "text"
+                                    && let Some(text) = content.content.get("text").and_then(|v| v.as_str())
+                                {
+                                    println!("  {}", text);
+                                }
+                            }
+                        }
+                        Err(e) => {
+                            eprintln!("Error calling sleep tool: {}", e);
+                        }
+                    }
+                } else {
+                    println!("Usage: /sleep <seconds>");
+                }
+            }
+            Some(&"/timeout") => match parts.get(1) {
+                Some(&"off") => {
+                    client.set_timeout(None);
+                    println!("Requests wait indefinitely");
+                }
+                Some(seconds) => match seconds.parse::<f64>().map(Duration::try_from_secs_f64) {
+                    Ok(Ok(timeout)) => {
+                        client.set_timeout(Some(timeout));
+                        println!("Requests are cancelled after {} seconds", seconds);
+                    }
+                    _ => println!("Usage: /timeout <seconds|off>"),
+                },
+                None => println!("Usage: /timeout <seconds|off>"),
+            },
+            Some(&"/resources") => {
+                match client.list_resources(None).await {
+                    Ok(result) => {
+                        println!("Available resources:");
+                        for resource in result.resources {
+                            println!("  - {} ({})", resource.uri, resource.name);
+                        }
+                    }
+                    Err(e) => eprintln!("Error listing resources: {}", e),
+                }
+                match client.list_resource_templates(None).await {
+                    Ok(result) => {
+                        println!("Resource templates:");
+                        for template in result.resource_templates {
+                            println!("  - {} ({})", template.uri_template, template.name);
+                        }
+                    }
+                    Err(e) => eprintln!("Error listing resource templates: {}", e),
+                }
+            }
+            Some(&"/read") => {
+                if let Some(uri) = parts.get(1) {
+                    match client.read_resource(uri.to_string()).await {
+                        Ok(result) => {
+                            for contents in result.contents {
+                                match contents {
+                                    ResourceContents::Text { uri, text, .. } => {
+                                        println!("{}:\n  {}", uri, text);
+                                    }
+                                    ResourceContents::Blob { uri, blob, .. } => {
+                                        println!("{}: {} bytes of base64 data", uri, blob.len());
+                                    }
+                                }
+                            }
+                        }
+                        Err(e) => eprintln!("Error reading resource: {}", e),
+                    }
+                } else {
+                    println!("Usage: /read <uri>");
+                }
+            }
+            Some(&"/prompts") => match client.list_prompts(None).await {
+                Ok(result) => {
+                    println!("Available prompts:");
+                    for prompt in result.prompts {
+                        println!("  - Name: {}", prompt.name);
+                        if let Some(description) = prompt.description {
+                            println!("    Description: {}", description);
+                        }
+                        for argument in prompt.arguments {
+                            let required = if argument.required { " (required)" } else { "" };
+                            println!("    Argument: {}{}", argument.name, required);
+                        }
+                    }
+                }
+                Err(e) => eprintln!("Error listing prompts: {}", e),
+            },
+            Some(&"/prompt") => {
+                if let Some(name) = parts.get(1) {
+                    let arguments: HashMap<String, String> = parts[2..]
+                        .iter()
+                        .filter_map(|arg| arg.split_once('='))
+                        .map(|(key, value)| (key.to_string(), value.to_string()))
+                        .collect();
+                    match client.get_prompt(name.to_string(), arguments).await {
+                        Ok(result) => {
+                            for message in result.messages {
+                                let text = message.content.content.get("text");
+                                let text = text.and_then(|v| v.as_str()).unwrap_or("<non-text content>");
+                                println!("  [{:?}] {}", message.role, text);
+                            }
+                        }
+                        Err(e) => eprintln!("Error getting prompt: {}", e),
+                    }
+                } else {
+                    println!("Usage: /prompt <name> [<arg>=<value> ...]");
+                }
+            }
+            Some(&"/loglevel") => {
+                let level = parts.get(1).map(|level| json!(level));
+                match level.map(serde_json::from_value::<LogLevel>) {
+                    Some(Ok(level)) => match client.set_log_level(level).await {
+                        Ok(()) => println!("Log level set to {:?}", level),
+                        Err(e) => eprintln!("Error setting log level: {}", e),
+                    },
+                    _ => println!(
+                        "Usage: /loglevel <debug|info|notice|warning|error|critical|alert|emergency>"
+                    ),
+                }
+            }
+            Some(&"/subscribe") => {
+                if let Some(uri) = parts.get(1) {
+                    match client.subscribe_resource(uri.to_string()).await {
+                        Ok(()) => println!("Subscribed to {}", uri),
+                        Err(e) => eprintln!("Error subscribing: {}", e),
+                    }
+                } else {
+                    println!("Usage: /subscribe <uri>");
+                }
+            }
+            Some(&"/unsubscribe") => {
+                if let Some(uri) = parts.get(1) {
+                    match client.unsubscribe_resource(uri.to_string()).await {
+                        Ok(()) => println!("Unsubscribed from {}", uri),
+                        Err(e) => eprintln!("Error unsubscribing: {}", e),
+                    }
+                } else {
+                    println!("Usage: /unsubscribe <uri>");
+                }
+            }
             Some(&"/quit") => {
                 println!("Goodbye!");
                 break;
             }
             _ => {
                 println!("Unknown command: {}", command);
-                println!("Available commands: /list, /greet <name>, /time, /quit");
+                println!(
+                    "Available commands: /list, /greet, /time, /sleep, /timeout, /resources, /read, /prompts, /prompt, \
+                     /loglevel, /subscribe, /unsubscribe, /quit"
+                );
             }
         }
     }
 
+    client.close().await?;
+
     Ok(())
 }
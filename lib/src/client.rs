@@ -1,10 +1,37 @@
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
-use std::io::{BufRead, BufReader, Read, Write};
-use std::process::{ChildStdin, ChildStdout, Command, Stdio};
+use std::collections::HashMap;
+use std::pin::Pin;
+use std::process::Command;
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::Duration;
+use tokio::sync::mpsc::{
+    UnboundedReceiver, UnboundedSender, WeakUnboundedSender, unbounded_channel,
+};
+use tokio::sync::oneshot;
+use tokio_stream::Stream;
 
-use crate::jsonrpc::{JsonRpcNotification, JsonRpcRequest, JsonRpcResponse};
-use crate::server::InitializeResult;
-use crate::types::{ToolsCallParams, ToolsCallResult, ToolsListParams, ToolsListResult};
+use crate::jsonrpc::{
+    JsonRpcError, JsonRpcMessage, JsonRpcNotification, JsonRpcRequest, JsonRpcResponse, RequestId,
+};
+use crate::Error;
+use crate::logging::{LogLevel, SetLevelParams};
+use crate::protocol::{self, LATEST_PROTOCOL_VERSION, SUPPORTED_PROTOCOL_VERSIONS};
+use crate::server::{InitializeResult, ServerCapabilities, ServerInfo};
+use crate::transport::{
+    ChildStdioTransport, LegacySseClientTransport, StreamableHttpClientTransport, Transport,
+    TransportReceiver, TransportSender,
+};
+use crate::types::{
+    CancelledParams, ProgressParams, ProgressToken, PromptsGetParams, PromptsGetResult,
+    PromptsListParams, PromptsListResult, RequestMeta,
+    ResourceTemplatesListParams, ResourceTemplatesListResult, ResourceUpdatedParams,
+    ResourcesListParams, ResourcesListResult, ResourcesReadParams, ResourcesReadResult,
+    ResourcesSubscribeParams, ResourcesUnsubscribeParams, ToolsCallParams, ToolsCallResult,
+    ToolsListParams, ToolsListResult,
+};
 
 #[derive(Debug, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -39,204 +66,837 @@ pub struct InitializeParams {
     pub client_info: ClientInfo,
 }
 
+/// Requests awaiting a response, by id. Each waiter receives the response, or an
+/// error if the request could not be sent.
+type Pending = Arc<
+    Mutex<HashMap<RequestId, oneshot::Sender<Result<JsonRpcResponse<serde_json::Value>, Error>>>>,
+>;
+
+/// Handles a notification from the server, given its params.
+pub type NotificationHandler = Arc<dyn Fn(Option<serde_json::Value>) + Send + Sync>;
+
+/// Handles a request from the server, given its params, returning the result
+/// to send back or an error.
+pub type RequestHandler =
+    Arc<dyn Fn(Option<serde_json::Value>) -> Result<serde_json::Value, JsonRpcError> + Send + Sync>;
+
+/// Handles progress reported on a call.
+pub type ProgressHandler = Arc<dyn Fn(ProgressParams) + Send + Sync>;
+
+/// Handlers for server-initiated messages, by method.
+#[derive(Default)]
+struct Handlers {
+    notifications: HashMap<String, NotificationHandler>,
+    requests: HashMap<String, RequestHandler>,
+    /// Listeners for `notifications/resources/updated`.
+    resource_updates: Vec<UnboundedSender<String>>,
+    /// Handlers for `notifications/progress`, by the progress token of their call.
+    progress: HashMap<ProgressToken, ProgressHandler>,
+}
+
+/// Unregisters a call's progress handler once the call completes or is dropped.
+struct ProgressRegistration<'a> {
+    handlers: &'a Mutex<Handlers>,
+    token: ProgressToken,
+}
+
+impl Drop for ProgressRegistration<'_> {
+    fn drop(&mut self) {
+        self.handlers.lock().unwrap().progress.remove(&self.token);
+    }
+}
+
+/// A stream of URIs of subscribed resources, yielded as the server reports
+/// them updated. Ends when the connection closes.
+pub struct ResourceUpdates {
+    receiver: UnboundedReceiver<String>,
+}
+
+impl ResourceUpdates {
+    /// Waits for the next updated URI.
+    pub async fn recv(&mut self) -> Option<String> {
+        self.receiver.recv().await
+    }
+}
+
+impl Stream for ResourceUpdates {
+    type Item = String;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<String>> {
+        self.receiver.poll_recv(cx)
+    }
+}
+
+/// A message queued for the writer thread, with the id of the request it carries.
+struct Outgoing {
+    message: String,
+    request_id: Option<RequestId>,
+}
+
+/// An MCP client that can have many requests in flight at once.
+///
+/// Requests get increasing ids, and a background reader routes each response to
+/// the call waiting for it, so responses may arrive in any order. Notifications
+/// and requests from the server go to the handlers registered for them. Transports
+/// block, so reading and writing happen on dedicated threads and the client
+/// works from any async runtime.
 pub struct Client {
-    pub stdin: ChildStdin,
-    pub reader: BufReader<ChildStdout>,
+    next_id: AtomicI64,
+    pending: Pending,
+    handlers: Arc<Mutex<Handlers>>,
+    /// Dropped on close, which tells the writer to close the transport.
+    outgoing: Option<UnboundedSender<Outgoing>>,
+    /// Resolves once the writer has closed the transport.
+    closed: Option<oneshot::Receiver<Result<(), Error>>>,
+    /// What the server told us about itself during initialization.
+    server: Option<InitializeResult>,
+    /// How long requests wait for a response before they are cancelled.
+    timeout: Option<Duration>,
+}
+
+/// Cancels a request on the server if its call is dropped, or gives up waiting,
+/// before the response arrives.
+struct CancelOnDrop<'a> {
+    client: &'a Client,
+    id: RequestId,
+    reason: &'static str,
+}
+
+impl Drop for CancelOnDrop<'_> {
+    fn drop(&mut self) {
+        // Nothing is pending once the response has arrived or sending failed
+        let Some(waiter) = self.client.pending.lock().unwrap().remove(&self.id) else {
+            return;
+        };
+        let _ = waiter.send(Err(Error::Cancelled));
+        eprintln!("Client: Cancelling request {}: {}", self.id, self.reason);
+        let notification = JsonRpcNotification {
+            jsonrpc: "2.0".to_string(),
+            method: "notifications/cancelled".to_string(),
+            params: Some(CancelledParams {
+                request_id: self.id.clone(),
+                reason: Some(self.reason.to_string()),
+            }),
+        };
+        if let Ok(message) = serde_json::to_string(&notification) {
+            let _ = self.client.queue(message, None);
+        }
+    }
 }
 
 impl Client {
     /// Spawns the server process and creates a new Client instance.
-    pub fn new(command: &str) -> Result<Self, Box<dyn std::error::Error>> {
-        let mut child = Command::new(command)
-            .stdin(Stdio::piped())
-            .stdout(Stdio::piped())
-            .spawn()?;
+    pub async fn new(command: &str) -> Result<Self, Error> {
+        Self::from_command(Command::new(command)).await
+    }
+
+    /// Spawns the server from a prepared command (arguments, environment, working
+    /// directory) and creates a new Client instance.
+    pub async fn from_command(command: Command) -> Result<Self, Error> {
+        let transport = ChildStdioTransport::spawn(command)?;
+        Self::connect(Box::new(transport)).await
+    }
+
+    /// Connects to a server at a Streamable HTTP endpoint URL and creates a new Client instance.
+    pub async fn connect_http(url: &str) -> Result<Self, Error> {
+        let transport = StreamableHttpClientTransport::new(url)?;
+        Self::connect(Box::new(transport)).await
+    }
 
-        let stdin = child.stdin.take().ok_or("Failed to take stdin")?;
-        let stdout = child.stdout.take().ok_or("Failed to take stdout")?;
-        let reader = BufReader::new(stdout);
+    /// Connects to a server using the legacy HTTP+SSE transport, given the URL of
+    /// its SSE endpoint, and creates a new Client instance.
+    pub async fn connect_sse(url: &str) -> Result<Self, Error> {
+        // Connecting blocks until the server announces its endpoint
+        let url = url.to_string();
+        let transport = tokio::task::spawn_blocking(move || {
+            LegacySseClientTransport::connect(&url).map_err(Error::from)
+        })
+        .await
+        .map_err(|e| Error::Io(std::io::Error::other(e)))??;
+        Self::connect(Box::new(transport)).await
+    }
 
-        let mut client = Client { stdin, reader };
+    /// Creates a new Client instance over an already established transport.
+    pub async fn connect(transport: Box<dyn Transport>) -> Result<Self, Error> {
+        let mut client = Self::start(transport);
 
         // Perform Initialization Phase
-        client.initialize()?;
+        client.server = Some(client.initialize().await?);
 
         Ok(client)
     }
 
+    /// Starts the reader and writer threads for the transport.
+    fn start(transport: Box<dyn Transport>) -> Self {
+        let (sender, receiver) = transport.split();
+        let pending = Pending::default();
+
+        let (outgoing, outgoing_receiver) = unbounded_channel();
+        let (closed_sender, closed) = oneshot::channel();
+        let writer_pending = pending.clone();
+        std::thread::spawn(move || {
+            let result = write_messages(sender, outgoing_receiver, &writer_pending);
+            let _ = closed_sender.send(result);
+        });
+        let handlers = Arc::new(Mutex::new(Handlers::default()));
+        let reader = Reader {
+            pending: pending.clone(),
+            handlers: handlers.clone(),
+            // Weak, so the reader doesn't keep the writer alive after close
+            outgoing: outgoing.downgrade(),
+        };
+        std::thread::spawn(move || reader.run(receiver));
+
+        Client {
+            next_id: AtomicI64::new(1),
+            pending,
+            handlers,
+            outgoing: Some(outgoing),
+            closed: Some(closed),
+            server: None,
+            timeout: None,
+        }
+    }
+
     /// Performs the JSON-RPC initialization handshake with the server.
-    fn initialize(&mut self) -> Result<(), Box<dyn std::error::Error>> {
-        println!("Client: Sending initialize request...");
-        let initialize_request = JsonRpcRequest {
-            jsonrpc: "2.0".to_string(),
-            id: 1,
-            method: "initialize".to_string(),
-            params: InitializeParams {
-                protocol_version: "2025-03-26".to_string(),
-                capabilities: ClientCapabilities {
-                    roots: Some(ClientRootsCapabilities {
-                        list_changed: Some(true),
-                    }),
-                    sampling: None,
-                    experimental: None,
-                },
-                client_info: ClientInfo {
-                    name: "ExampleClient".to_string(),
-                    version: "1.0.0".to_string(),
-                },
+    async fn initialize(&self) -> Result<InitializeResult, Error> {
+        eprintln!("Client: Sending initialize request...");
+        let params = InitializeParams {
+            protocol_version: LATEST_PROTOCOL_VERSION.to_string(),
+            capabilities: ClientCapabilities {
+                roots: Some(ClientRootsCapabilities {
+                    list_changed: Some(true),
+                }),
+                sampling: None,
+                experimental: None,
+            },
+            client_info: ClientInfo {
+                name: "ExampleClient".to_string(),
+                version: "1.0.0".to_string(),
             },
         };
-        self.send_request(&initialize_request)?;
+        let result: InitializeResult = self.request("initialize", params).await?;
+        eprintln!("Client received: {:?}", result);
+
+        // The server may answer with an older version; proceed only if we speak it too
+        if !SUPPORTED_PROTOCOL_VERSIONS.contains(&result.protocol_version.as_str()) {
+            return Err(Error::VersionMismatch {
+                offered: result.protocol_version,
+                supported: SUPPORTED_PROTOCOL_VERSIONS.iter().map(|v| v.to_string()).collect(),
+            });
+        }
+        // Later requests are checked against result.capabilities
+
+        eprintln!("Client: Sending initialized notification...");
+        self.send_notification("notifications/initialized")?;
+        eprintln!("Client: Initialization complete.");
+
+        Ok(result)
+    }
+
+    /// The capabilities the server advertised during initialization.
+    pub fn server_capabilities(&self) -> Option<&ServerCapabilities> {
+        self.server.as_ref().map(|server| &server.capabilities)
+    }
+
+    /// Sets how long requests wait for a response. A request that times out is
+    /// cancelled on the server and fails with [`Error::Timeout`]. By default
+    /// requests wait indefinitely.
+    pub fn set_timeout(&mut self, timeout: Option<Duration>) {
+        self.timeout = timeout;
+    }
+
+    /// The protocol version agreed on during initialization.
+    pub fn protocol_version(&self) -> Option<&str> {
+        self.server.as_ref().map(|server| server.protocol_version.as_str())
+    }
+
+    /// Returns true if the agreed protocol version allows JSON-RPC batches.
+    pub fn supports_batching(&self) -> bool {
+        self.protocol_version().is_some_and(protocol::supports_batching)
+    }
+
+    /// The name and version the server reported during initialization.
+    pub fn server_info(&self) -> Option<&ServerInfo> {
+        self.server.as_ref().map(|server| &server.server_info)
+    }
+
+    /// Returns true if the server advertised the capability `method` belongs to.
+    pub fn supports(&self, method: &str) -> bool {
+        self.server_capabilities()
+            .is_none_or(|capabilities| capabilities.supports(method))
+    }
+
+    /// Registers a handler for notifications from the server with the given method,
+    /// such as `notifications/tools/list_changed`. Handlers run on the reader
+    /// thread, in the order notifications arrive, so they should return quickly.
+    pub fn on_notification<F>(&self, method: &str, handler: F)
+    where
+        F: Fn(Option<serde_json::Value>) + Send + Sync + 'static,
+    {
+        let mut handlers = self.handlers.lock().unwrap();
+        handlers
+            .notifications
+            .insert(method.to_string(), Arc::new(handler));
+    }
 
-        println!("Client: Reading initialize response...");
-        let initialize_response: JsonRpcResponse<InitializeResult> = self.read_response()?;
-        println!("Client received: {:?}", initialize_response);
+    /// Registers a handler for requests from the server with the given method,
+    /// such as `roots/list` or `sampling/createMessage`. Each request runs on
+    /// its own thread, so a slow handler doesn't hold up responses.
+    pub fn on_request<F>(&self, method: &str, handler: F)
+    where
+        F: Fn(Option<serde_json::Value>) -> Result<serde_json::Value, JsonRpcError>
+            + Send
+            + Sync
+            + 'static,
+    {
+        let mut handlers = self.handlers.lock().unwrap();
+        handlers.requests.insert(method.to_string(), Arc::new(handler));
+    }
 
-        // Check for initialization errors or version mismatch (basic check for now)
-        if let Some(error) = initialize_response.error {
-            return Err(format!("Initialization failed: {:?}", error).into());
+    /// Sends a JSON-RPC request and waits for its response, returning the result
+    /// or the server's error. Methods the server didn't advertise fail without
+    /// being sent. Other requests may be sent while this one waits.
+    /// Dropping the returned future before it completes cancels the request.
+    pub async fn request<P: Serialize, R: DeserializeOwned>(
+        &self,
+        method: &str,
+        params: P,
+    ) -> Result<R, Error> {
+        if !self.supports(method) {
+            // Fail the way the server would, without a round trip
+            return Err(Error::Protocol(JsonRpcError {
+                code: -32601, // Method not found
+                message: format!("Server does not support {}", method),
+                data: None,
+            }));
         }
-        if let Some(result) = initialize_response.result {
-            if result.protocol_version != "2025-03-26" {
-                return Err(format!(
-                    "Unsupported server protocol version: {}",
-                    result.protocol_version
-                )
-                .into());
-            }
-            // TODO: Negotiate capabilities based on result.capabilities
-        } else {
-            return Err("Initialization response missing result or error".into());
+        let id = RequestId::Number(self.next_id.fetch_add(1, Ordering::Relaxed));
+        let request = JsonRpcRequest {
+            jsonrpc: "2.0".to_string(),
+            id: id.clone(),
+            method: method.to_string(),
+            params,
+        };
+        let message = serde_json::to_string(&request)?;
+
+        let (sender, receiver) = oneshot::channel();
+        self.pending.lock().unwrap().insert(id.clone(), sender);
+        if let Err(e) = self.queue(message, Some(id.clone())) {
+            self.pending.lock().unwrap().remove(&id);
+            return Err(e);
+        }
+
+        // The server must not be asked to cancel initialization
+        let mut guard = (method != "initialize").then(|| CancelOnDrop {
+            client: self,
+            id,
+            reason: "The request was abandoned",
+        });
+        let response = match self.timeout {
+            None => receiver.await,
+            Some(timeout) => match tokio::time::timeout(timeout, receiver).await {
+                Ok(response) => response,
+                Err(_) => {
+                    if let Some(guard) = &mut guard {
+                        guard.reason = "The request timed out";
+                    }
+                    return Err(Error::Timeout);
+                }
+            },
+        };
+        let response = response.map_err(|_| Error::Cancelled)??;
+        if let Some(error) = response.error {
+            return Err(Error::Protocol(error));
         }
+        let result = response.result.unwrap_or_default();
+        Ok(serde_json::from_value(result)?)
+    }
 
-        println!("Client: Sending initialized notification...");
-        let initialized_notification = JsonRpcNotification {
+    /// Sends a JSON-RPC notification to the server.
+    pub fn send_notification(&self, method: &str) -> Result<(), Error> {
+        let notification: JsonRpcNotification = JsonRpcNotification {
             jsonrpc: "2.0".to_string(),
-            method: "notifications/initialized".to_string(),
+            method: method.to_string(),
+            params: None,
         };
-        self.send_notification(&initialized_notification)?;
-        println!("Client: Initialization complete.");
+        self.queue(serde_json::to_string(&notification)?, None)
+    }
 
+    fn queue(&self, message: String, request_id: Option<RequestId>) -> Result<(), Error> {
+        let outgoing = self.outgoing.as_ref().ok_or_else(|| Error::closed("Client is closed"))?;
+        outgoing
+            .send(Outgoing {
+                message,
+                request_id,
+            })
+            .map_err(|_| Error::closed("Connection to the server is closed"))?;
         Ok(())
     }
 
-    /// Sends a JSON-RPC request to the server.
-    pub fn send_request<T: serde::Serialize>(
-        &mut self,
-        request: &JsonRpcRequest<T>,
-    ) -> Result<(), Box<dyn std::error::Error>> {
-        let request_str = serde_json::to_string(request)?;
-        write!(
-            self.stdin,
-            "Content-Length: {}\r\n\r\n{}",
-            request_str.len(),
-            request_str
-        )?;
-        self.stdin.flush()?;
+    /// Closes the connection to the server once queued messages have been sent.
+    pub async fn close(&mut self) -> Result<(), Error> {
+        self.outgoing = None;
+        if let Some(closed) = self.closed.take() {
+            closed
+                .await
+                .map_err(|_| Error::closed("Transport writer stopped unexpectedly"))??;
+        }
         Ok(())
     }
 
-    /// Sends a JSON-RPC notification to the server.
-    fn send_notification(
-        &mut self,
-        notification: &JsonRpcNotification,
-    ) -> Result<(), Box<dyn std::error::Error>> {
-        let notification_str = serde_json::to_string(notification)?;
-        write!(
-            self.stdin,
-            "Content-Length: {}\r\n\r\n{}",
-            notification_str.len(),
-            notification_str
-        )?;
-        self.stdin.flush()?;
+    /// Sends a tools/list request to the server and returns the result.
+    pub async fn list_tools(
+        &self,
+        cursor: Option<String>,
+    ) -> Result<ToolsListResult, Error> {
+        eprintln!("Client: Sending tools/list request...");
+        let result = self.request("tools/list", ToolsListParams { cursor }).await?;
+        eprintln!("Client received: {:?}", result);
+        Ok(result)
+    }
+
+    /// Sends a tools/call request to the server and returns the result.
+    pub async fn call_tool(
+        &self,
+        name: String,
+        arguments: serde_json::Value,
+    ) -> Result<ToolsCallResult, Error> {
+        eprintln!("Client: Sending tools/call request for '{}'...", name);
+        let result = self
+            .request("tools/call", ToolsCallParams { name, arguments, meta: None })
+            .await?;
+        eprintln!("Client received: {:?}", result);
+        Ok(result)
+    }
+
+    /// Like [`call_tool`](Self::call_tool), but asks the server to report progress,
+    /// passing each report to `on_progress`. It runs on the reader thread, so it
+    /// should return quickly.
+    pub async fn call_tool_with_progress<F>(
+        &self,
+        name: String,
+        arguments: serde_json::Value,
+        on_progress: F,
+    ) -> Result<ToolsCallResult, Error>
+    where
+        F: Fn(ProgressParams) + Send + Sync + 'static,
+    {
+        eprintln!("Client: Sending tools/call request for '{}' with progress...", name);
+        // Tokens come from the request id counter, so no two calls share one
+        let token = RequestId::Number(self.next_id.fetch_add(1, Ordering::Relaxed));
+        self.handlers
+            .lock()
+            .unwrap()
+            .progress
+            .insert(token.clone(), Arc::new(on_progress));
+        let _registration = ProgressRegistration {
+            handlers: &self.handlers,
+            token: token.clone(),
+        };
+        let meta = RequestMeta {
+            progress_token: Some(token),
+        };
+        let result = self
+            .request("tools/call", ToolsCallParams { name, arguments, meta: Some(meta) })
+            .await?;
+        eprintln!("Client received: {:?}", result);
+        Ok(result)
+    }
+
+    /// Sends a resources/list request to the server and returns one page of resources.
+    pub async fn list_resources(
+        &self,
+        cursor: Option<String>,
+    ) -> Result<ResourcesListResult, Error> {
+        eprintln!("Client: Sending resources/list request...");
+        let result = self
+            .request("resources/list", ResourcesListParams { cursor })
+            .await?;
+        eprintln!("Client received: {:?}", result);
+        Ok(result)
+    }
+
+    /// Sends a resources/templates/list request to the server and returns one page of templates.
+    pub async fn list_resource_templates(
+        &self,
+        cursor: Option<String>,
+    ) -> Result<ResourceTemplatesListResult, Error> {
+        eprintln!("Client: Sending resources/templates/list request...");
+        let result = self
+            .request(
+                "resources/templates/list",
+                ResourceTemplatesListParams { cursor },
+            )
+            .await?;
+        eprintln!("Client received: {:?}", result);
+        Ok(result)
+    }
+
+    /// Asks the server to send log messages at `level` and above, which arrive as
+    /// `notifications/message`.
+    pub async fn set_log_level(&self, level: LogLevel) -> Result<(), Error> {
+        eprintln!("Client: Sending logging/setLevel request ({:?})...", level);
+        let _: serde_json::Value = self
+            .request("logging/setLevel", SetLevelParams { level })
+            .await?;
         Ok(())
     }
 
-    /// Reads a JSON-RPC response from the server.
-    pub fn read_response<T: serde::de::DeserializeOwned>(
-        &mut self,
-    ) -> Result<JsonRpcResponse<T>, Box<dyn std::error::Error>> {
-        // Read headers
-        let mut headers = String::new();
-        loop {
-            let mut line = String::new();
-            self.reader.read_line(&mut line)?;
-            if line == "\r\n" {
-                break;
+    /// Sends a prompts/list request to the server and returns one page of prompts.
+    pub async fn list_prompts(
+        &self,
+        cursor: Option<String>,
+    ) -> Result<PromptsListResult, Error> {
+        eprintln!("Client: Sending prompts/list request...");
+        let result = self
+            .request("prompts/list", PromptsListParams { cursor })
+            .await?;
+        eprintln!("Client received: {:?}", result);
+        Ok(result)
+    }
+
+    /// Sends a prompts/get request to the server and returns the rendered prompt.
+    pub async fn get_prompt(
+        &self,
+        name: String,
+        arguments: HashMap<String, String>,
+    ) -> Result<PromptsGetResult, Error> {
+        eprintln!("Client: Sending prompts/get request for '{}'...", name);
+        let result = self
+            .request("prompts/get", PromptsGetParams { name, arguments })
+            .await?;
+        eprintln!("Client received: {:?}", result);
+        Ok(result)
+    }
+
+    /// Subscribes to updates of the resource at `uri`. Updates arrive on the
+    /// streams returned by [`Client::resource_updates`].
+    pub async fn subscribe_resource(&self, uri: String) -> Result<(), Error> {
+        eprintln!("Client: Sending resources/subscribe request for '{}'...", uri);
+        let _: serde_json::Value = self
+            .request("resources/subscribe", ResourcesSubscribeParams { uri })
+            .await?;
+        Ok(())
+    }
+
+    /// Stops updates of the resource at `uri`.
+    pub async fn unsubscribe_resource(&self, uri: String) -> Result<(), Error> {
+        eprintln!("Client: Sending resources/unsubscribe request for '{}'...", uri);
+        let _: serde_json::Value = self
+            .request("resources/unsubscribe", ResourcesUnsubscribeParams { uri })
+            .await?;
+        Ok(())
+    }
+
+    /// Returns a stream of the URIs of subscribed resources as the server
+    /// reports them updated. Each stream sees every update from the moment it
+    /// is created.
+    pub fn resource_updates(&self) -> ResourceUpdates {
+        let (sender, receiver) = unbounded_channel();
+        self.handlers.lock().unwrap().resource_updates.push(sender);
+        ResourceUpdates { receiver }
+    }
+
+    /// Sends a resources/read request to the server and returns the resource's contents.
+    pub async fn read_resource(&self, uri: String) -> Result<ResourcesReadResult, Error> {
+        eprintln!("Client: Sending resources/read request for '{}'...", uri);
+        let result = self
+            .request("resources/read", ResourcesReadParams { uri })
+            .await?;
+        eprintln!("Client received: {:?}", result);
+        Ok(result)
+    }
+}
+
+/// Writes queued messages until the client is closed or dropped, then closes
+/// the transport. A request that can't be sent fails its waiting call.
+fn write_messages(
+    mut sender: Box<dyn TransportSender>,
+    mut outgoing: UnboundedReceiver<Outgoing>,
+    pending: &Pending,
+) -> Result<(), Error> {
+    while let Some(Outgoing {
+        message,
+        request_id,
+    }) = outgoing.blocking_recv()
+    {
+        if let Err(e) = sender.send(&message) {
+            eprintln!("Client: Failed to send message: {}", e);
+            if let Some(waiter) = request_id.and_then(|id| pending.lock().unwrap().remove(&id)) {
+                let _ = waiter.send(Err(Error::from(e)));
             }
-            headers.push_str(&line);
         }
+    }
+    sender.close().map_err(Error::from)
+}
 
-        let content_length = headers
-            .lines()
-            .find_map(|line| line.strip_prefix("Content-Length: "))
-            .and_then(|s| s.trim().parse::<usize>().ok())
-            .ok_or("Missing Content-Length header")?;
+/// Reads messages from the server and sorts them into responses, notifications
+/// and requests.
+struct Reader {
+    pending: Pending,
+    handlers: Arc<Mutex<Handlers>>,
+    outgoing: WeakUnboundedSender<Outgoing>,
+}
 
-        // Read body
-        let mut body = vec![0; content_length];
-        self.reader.read_exact(&mut body)?;
-        let response: JsonRpcResponse<T> = serde_json::from_slice(&body)?;
+impl Reader {
+    /// Dispatches messages until the server closes the connection. Calls still
+    /// waiting for a response then fail as cancelled.
+    fn run(self, mut receiver: Box<dyn TransportReceiver>) {
+        loop {
+            let body = match receiver.receive() {
+                Ok(Some(body)) => body,
+                Ok(None) => break,
+                Err(e) => match Error::from(e) {
+                    // The message was read whole, so later ones can still be read
+                    Error::InvalidUtf8(details) => {
+                        eprintln!("Client: Ignoring message that is not valid UTF-8: {}", details);
+                        continue;
+                    }
+                    e => {
+                        eprintln!("Client: Error reading from server: {}", e);
+                        break;
+                    }
+                },
+            };
+            match serde_json::from_str(&body) {
+                Ok(message) => self.dispatch(message),
+                Err(e) => self.handle_malformed(&body, e),
+            }
+        }
+        for (_, waiter) in self.pending.lock().unwrap().drain() {
+            let _ = waiter.send(Err(Error::Cancelled));
+        }
+    }
 
-        Ok(response)
+    fn dispatch(&self, message: JsonRpcMessage) {
+        match message {
+            JsonRpcMessage::Request(request) => {
+                self.handle_request(&request.method, request.id, request.params)
+            }
+            JsonRpcMessage::Notification(notification) => {
+                self.handle_notification(&notification.method, notification.params)
+            }
+            JsonRpcMessage::Response(response) | JsonRpcMessage::Error(response) => {
+                self.handle_response(response)
+            }
+        }
     }
 
-    /// Sends a tools/list request to the server and returns the result.
-    pub fn list_tools(
-        &mut self,
-        cursor: Option<String>,
-    ) -> Result<ToolsListResult, Box<dyn std::error::Error>> {
-        println!("Client: Sending tools/list request...");
-        // TODO: Use a proper request ID manager
-        let request = JsonRpcRequest {
-            jsonrpc: "2.0".to_string(),
-            id: 2, // Assuming initialize used ID 1
-            method: "tools/list".to_string(),
-            params: ToolsListParams { cursor },
+    /// Fails the call waiting for a response that couldn't be parsed, if its id
+    /// can still be read, so the call doesn't wait forever.
+    fn handle_malformed(&self, body: &str, e: serde_json::Error) {
+        let id = serde_json::from_str::<serde_json::Value>(body)
+            .ok()
+            .filter(|message| message.get("method").is_none())
+            .and_then(|message| serde_json::from_value::<RequestId>(message.get("id")?.clone()).ok());
+        let waiter = id.as_ref().and_then(|id| self.pending.lock().unwrap().remove(id));
+        let (Some(id), Some(waiter)) = (id, waiter) else {
+            eprintln!("Client: Ignoring malformed message: {}", e);
+            return;
         };
-        self.send_request(&request)?;
+        eprintln!("Client: Malformed response to request {}: {}", id, e);
+        let _ = waiter.send(Err(Error::Protocol(JsonRpcError {
+            code: -32600, // Invalid Request
+            message: format!("Malformed response: {}", e),
+            data: None,
+        })));
+    }
 
-        println!("Client: Reading tools/list response...");
-        let response: JsonRpcResponse<ToolsListResult> = self.read_response()?;
-        println!("Client received: {:?}", response);
+    /// Routes a response to the call waiting for it. An error without an id is
+    /// about a request the server couldn't read, so it fails every waiting call
+    /// that might have been that request.
+    fn handle_response(&self, response: JsonRpcResponse<serde_json::Value>) {
+        let Some(id) = response.id.clone() else {
+            eprintln!("Client: Server reported an error: {:?}", response.error);
+            let error = response.error.unwrap_or_else(|| JsonRpcError {
+                code: -32603, // Internal error
+                message: "Response without an id".to_string(),
+                data: None,
+            });
+            for (_, waiter) in self.pending.lock().unwrap().drain() {
+                let _ = waiter.send(Err(Error::Protocol(error.clone())));
+            }
+            return;
+        };
+        match self.pending.lock().unwrap().remove(&id) {
+            Some(waiter) => {
+                let _ = waiter.send(Ok(response));
+            }
+            None => eprintln!("Client: Ignoring response to unknown request {}", id),
+        }
+    }
 
-        if let Some(error) = response.error {
-            return Err(format!("tools/list request failed: {:?}", error).into());
+    fn handle_notification(&self, method: &str, params: Option<serde_json::Value>) {
+        let mut handlers = self.handlers.lock().unwrap();
+        let mut handled = false;
+        if method == "notifications/progress" {
+            let progress = params
+                .clone()
+                .and_then(|p| serde_json::from_value::<ProgressParams>(p).ok());
+            let handler = progress
+                .as_ref()
+                .and_then(|progress| handlers.progress.get(&progress.progress_token).cloned());
+            if let (Some(progress), Some(handler)) = (progress, handler) {
+                drop(handlers);
+                handler(progress);
+                return;
+            }
         }
-        if let Some(result) = response.result {
-            Ok(result)
-        } else {
-            Err("tools/list response missing result or error".into())
+        if method == "notifications/resources/updated" {
+            let updated = params
+                .clone()
+                .and_then(|p| serde_json::from_value::<ResourceUpdatedParams>(p).ok());
+            if let Some(updated) = updated {
+                let listeners = &mut handlers.resource_updates;
+                listeners.retain(|listener| listener.send(updated.uri.clone()).is_ok());
+                handled = !listeners.is_empty();
+            }
+        }
+        let handler = handlers.notifications.get(method).cloned();
+        drop(handlers);
+        match handler {
+            Some(handler) => handler(params),
+            None if handled => {}
+            None => eprintln!("Client: Ignoring notification '{}'", method),
         }
     }
 
-    /// Sends a tools/call request to the server and returns the result.
-    pub fn call_tool(
-        &mut self,
-        name: String,
-        arguments: serde_json::Value,
-    ) -> Result<ToolsCallResult, Box<dyn std::error::Error>> {
-        println!("Client: Sending tools/call request for '{}'...", name);
-        // TODO: Use a proper request ID manager
-        let request = JsonRpcRequest {
-            jsonrpc: "2.0".to_string(),
-            id: 3, // Increment from previous requests
-            method: "tools/call".to_string(),
-            params: ToolsCallParams { name, arguments },
+    /// Answers a request from the server on its own thread.
+    fn handle_request(
+        &self,
+        method: &str,
+        id: RequestId,
+        params: Option<serde_json::Value>,
+    ) {
+        let handler = self.handlers.lock().unwrap().requests.get(method).cloned();
+        let Some(outgoing) = self.outgoing.upgrade() else {
+            return; // Closing; nobody to answer
         };
-        self.send_request(&request)?;
+        let method = method.to_string();
+        std::thread::spawn(move || {
+            let result = match handler {
+                Some(handler) => handler(params),
+                // Servers may ping at any time to check the client is alive
+                None if method == "ping" => Ok(serde_json::json!({})),
+                None => Err(JsonRpcError {
+                    code: -32601, // Method not found
+                    message: format!("Method not found: '{}'", method),
+                    data: None,
+                }),
+            };
+            let response = JsonRpcResponse {
+                jsonrpc: "2.0".to_string(),
+                error: result.as_ref().err().cloned(),
+                result: result.ok(),
+                id: Some(id),
+            };
+            let Ok(message) = serde_json::to_string(&response) else {
+                return;
+            };
+            let _ = outgoing.send(Outgoing {
+                message,
+                request_id: None,
+            });
+        });
+    }
+}
 
-        println!("Client: Reading tools/call response...");
-        let response: JsonRpcResponse<ToolsCallResult> = self.read_response()?;
-        println!("Client received: {:?}", response);
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transport::MemoryTransport;
+    use serde_json::{Value, json};
 
-        if let Some(error) = response.error {
-            return Err(format!("tools/call request failed: {:?}", error).into());
-        }
-        if let Some(result) = response.result {
-            Ok(result)
-        } else {
-            Err("tools/call response missing result or error".into())
-        }
+    /// Starts a client without initializing, against a fake server that answers
+    /// each request with whatever `reply` makes of it.
+    fn start<F>(reply: F) -> Client
+    where
+        F: Fn(&Value) -> Value + Send + 'static,
+    {
+        start_recording(move |request| Some(reply(request))).0
+    }
+
+    /// Like [`start`], but requests `reply` returns None for go unanswered, and
+    /// notifications the fake server receives are passed on.
+    fn start_recording<F>(reply: F) -> (Client, std::sync::mpsc::Receiver<Value>)
+    where
+        F: Fn(&Value) -> Option<Value> + Send + 'static,
+    {
+        let (client_end, mut server_end) = MemoryTransport::pair();
+        let (notifications, received) = std::sync::mpsc::channel();
+        std::thread::spawn(move || {
+            while let Ok(Some(message)) = server_end.receive() {
+                let message: Value = serde_json::from_str(&message).unwrap();
+                if message.get("id").is_none() {
+                    let _ = notifications.send(message);
+                } else if let Some(response) = reply(&message) {
+                    server_end.send(&response.to_string()).unwrap();
+                }
+            }
+        });
+        (Client::start(Box::new(client_end)), received)
+    }
+
+    #[tokio::test]
+    async fn malformed_responses_fail_their_call() {
+        // Neither a result nor an error, but the id can still be read
+        let client = start(|request| json!({ "jsonrpc": "2.0", "id": request["id"] }));
+        let error = client.request::<_, Value>("ping", json!({})).await.unwrap_err();
+        assert_eq!(error.code(), Some(-32600), "{}", error);
+    }
+
+    #[tokio::test]
+    async fn errors_without_an_id_fail_the_outstanding_call() {
+        let client = start(|_| {
+            json!({
+                "jsonrpc": "2.0",
+                "error": { "code": -32700, "message": "Parse error" },
+                "id": null,
+            })
+        });
+        let error = client.request::<_, Value>("ping", json!({})).await.unwrap_err();
+        assert_eq!(error.code(), Some(-32700), "{}", error);
+    }
+
+    #[tokio::test]
+    async fn calls_in_flight_when_the_connection_closes_are_cancelled() {
+        let (client_end, mut server_end) = MemoryTransport::pair();
+        std::thread::spawn(move || {
+            // Read the request, then go away without answering
+            let _ = server_end.receive();
+        });
+        let client = Client::start(Box::new(client_end));
+        let error = client.request::<_, Value>("ping", json!({})).await.unwrap_err();
+        assert!(matches!(error, Error::Cancelled), "{}", error);
+    }
+
+    fn cancelled_request(received: &std::sync::mpsc::Receiver<Value>) -> Value {
+        let notification = received.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert_eq!(notification["method"], "notifications/cancelled");
+        notification["params"].clone()
+    }
+
+    #[tokio::test]
+    async fn timed_out_calls_are_cancelled_on_the_server() {
+        let (mut client, received) = start_recording(|_| None);
+        client.set_timeout(Some(Duration::from_millis(50)));
+        let error = client.request::<_, Value>("ping", json!({})).await.unwrap_err();
+        assert!(matches!(error, Error::Timeout), "{}", error);
+
+        let params = cancelled_request(&received);
+        assert_eq!(params["requestId"], 1);
+        assert_eq!(params["reason"], "The request timed out");
+    }
+
+    #[tokio::test]
+    async fn dropped_calls_are_cancelled_on_the_server() {
+        let (client, received) = start_recording(|_| None);
+        let call = client.request::<_, Value>("ping", json!({}));
+        assert!(tokio::time::timeout(Duration::from_millis(50), call).await.is_err());
+
+        let params = cancelled_request(&received);
+        assert_eq!(params["requestId"], 1);
+        assert_eq!(params["reason"], "The request was abandoned");
     }
 }
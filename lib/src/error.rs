@@ -0,0 +1,118 @@
+use std::fmt;
+use std::io;
+
+use crate::jsonrpc::JsonRpcError;
+
+/// Errors from the client and server APIs.
+#[derive(Debug)]
+pub enum Error {
+    /// Reading from or writing to the transport failed, or the connection is closed.
+    Io(io::Error),
+    /// A message couldn't be read off the wire, e.g. a bad `Content-Length` header.
+    Framing(String),
+    /// A whole message arrived but isn't valid UTF-8. Unlike a framing error the
+    /// stream is still in sync, so later messages can be read.
+    InvalidUtf8(String),
+    /// A message wasn't valid JSON or didn't have the expected shape.
+    Json(serde_json::Error),
+    /// The peer answered with a JSON-RPC error.
+    Protocol(JsonRpcError),
+    /// No response arrived in time.
+    Timeout,
+    /// The request was cancelled before a response arrived, e.g. because the
+    /// connection closed while it was in flight.
+    Cancelled,
+    /// The server offered a protocol version this side doesn't speak.
+    VersionMismatch {
+        offered: String,
+        supported: Vec<String>,
+    },
+}
+
+impl Error {
+    /// An error for a connection that is no longer usable.
+    pub(crate) fn closed(message: &str) -> Self {
+        Error::Io(io::Error::new(io::ErrorKind::NotConnected, message.to_string()))
+    }
+
+    /// The JSON-RPC error code, if the peer answered with an error.
+    pub fn code(&self) -> Option<i64> {
+        match self {
+            Error::Protocol(error) => Some(error.code),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Io(e) => write!(f, "Transport error: {}", e),
+            Error::Framing(message) => write!(f, "Framing error: {}", message),
+            Error::InvalidUtf8(details) => write!(f, "Message is not valid UTF-8: {}", details),
+            Error::Json(e) => write!(f, "Invalid JSON: {}", e),
+            Error::Protocol(error) => {
+                write!(f, "JSON-RPC error {}: {}", error.code, error.message)?;
+                if let Some(data) = &error.data {
+                    write!(f, " ({})", data)?;
+                }
+                Ok(())
+            }
+            Error::Timeout => write!(f, "Request timed out"),
+            Error::Cancelled => write!(f, "Request was cancelled"),
+            Error::VersionMismatch { offered, supported } => write!(
+                f,
+                "Unsupported protocol version {} (supported: {})",
+                offered,
+                supported.join(", ")
+            ),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(e) => Some(e),
+            Error::Json(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        Error::Io(e)
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(e: serde_json::Error) -> Self {
+        Error::Json(e)
+    }
+}
+
+impl From<JsonRpcError> for Error {
+    fn from(error: JsonRpcError) -> Self {
+        Error::Protocol(error)
+    }
+}
+
+/// Transports report errors as `Box<dyn Error>`; this recovers the kind where
+/// it is known and treats anything else as a transport failure.
+impl From<Box<dyn std::error::Error>> for Error {
+    fn from(e: Box<dyn std::error::Error>) -> Self {
+        let e = match e.downcast::<Error>() {
+            Ok(e) => return *e,
+            Err(e) => e,
+        };
+        let e = match e.downcast::<io::Error>() {
+            Ok(e) => return Error::Io(*e),
+            Err(e) => e,
+        };
+        match e.downcast::<serde_json::Error>() {
+            Ok(e) => Error::Json(*e),
+            Err(e) => Error::Io(io::Error::other(e.to_string())),
+        }
+    }
+}
@@ -1,4 +1,8 @@
+use serde::de::{self, Deserializer};
+use serde::ser::{self, SerializeMap, Serializer};
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::fmt;
 
 #[derive(Debug, Serialize, Deserialize)]
 #[serde(rename_all = "lowercase")]
@@ -6,32 +10,271 @@ pub enum JsonRpcVersion {
     V2,
 }
 
+/// Identifies a request. JSON-RPC allows numbers and strings, and a response
+/// carries the id of its request unchanged.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum RequestId {
+    Number(i64),
+    String(String),
+}
+
+impl fmt::Display for RequestId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RequestId::Number(id) => write!(f, "{}", id),
+            RequestId::String(id) => write!(f, "\"{}\"", id),
+        }
+    }
+}
+
+impl From<i64> for RequestId {
+    fn from(id: i64) -> Self {
+        RequestId::Number(id)
+    }
+}
+
+impl From<String> for RequestId {
+    fn from(id: String) -> Self {
+        RequestId::String(id)
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct JsonRpcRequest<T> {
     pub jsonrpc: String, // Always "2.0"
     pub method: String,
     pub params: T,
-    pub id: u64,
+    pub id: RequestId,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+/// A response carries exactly one of `result` and `error`; (de)serializing one
+/// with both or neither fails. The id is None only for errors about a request
+/// whose id couldn't be read.
+#[derive(Debug)]
 pub struct JsonRpcResponse<T> {
     pub jsonrpc: String,
     pub result: Option<T>,
     pub error: Option<JsonRpcError>,
-    pub id: u64,
+    pub id: Option<RequestId>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+impl<T: Serialize> Serialize for JsonRpcResponse<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(3))?;
+        map.serialize_entry("jsonrpc", &self.jsonrpc)?;
+        match (&self.result, &self.error) {
+            (Some(result), None) => map.serialize_entry("result", result)?,
+            (None, Some(error)) => map.serialize_entry("error", error)?,
+            _ => {
+                return Err(ser::Error::custom(
+                    "a response must have exactly one of result and error",
+                ));
+            }
+        }
+        map.serialize_entry("id", &self.id)?;
+        map.end()
+    }
+}
+
+impl<'de, T: Deserialize<'de>> Deserialize<'de> for JsonRpcResponse<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(bound(deserialize = "T: Deserialize<'de>"))]
+        struct Raw<T> {
+            jsonrpc: String,
+            // A null result is still a result, so only a missing field is None
+            #[serde(default, deserialize_with = "present")]
+            result: Option<T>,
+            error: Option<JsonRpcError>,
+            id: Option<RequestId>,
+        }
+
+        fn present<'de, D: Deserializer<'de>, T: Deserialize<'de>>(
+            deserializer: D,
+        ) -> Result<Option<T>, D::Error> {
+            T::deserialize(deserializer).map(Some)
+        }
+
+        let raw = Raw::<T>::deserialize(deserializer)?;
+        if raw.result.is_some() == raw.error.is_some() {
+            return Err(de::Error::custom(
+                "a response must have exactly one of result and error",
+            ));
+        }
+        Ok(JsonRpcResponse {
+            jsonrpc: raw.jsonrpc,
+            result: raw.result,
+            error: raw.error,
+            id: raw.id,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct JsonRpcError {
     pub code: i64,
     pub message: String,
+    /// Details about the error, such as which params were invalid.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub data: Option<Value>,
 }
 
-// A simple struct for notifications without parameters
 #[derive(Debug, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
-pub struct JsonRpcNotification {
+pub struct JsonRpcNotification<T = Value> {
     pub jsonrpc: String, // Always "2.0"
     pub method: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub params: Option<T>,
+}
+
+/// Any single JSON-RPC message, told apart by its fields: requests have a method
+/// and an id, notifications a method only, and responses an id with a result or
+/// an error.
+#[derive(Debug)]
+pub enum JsonRpcMessage {
+    Request(JsonRpcRequest<Option<Value>>),
+    Notification(JsonRpcNotification),
+    /// A response carrying a result.
+    Response(JsonRpcResponse<Value>),
+    /// A response carrying an error.
+    Error(JsonRpcResponse<Value>),
+}
+
+impl Serialize for JsonRpcMessage {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        #[derive(Serialize)]
+        struct Request<'a> {
+            jsonrpc: &'a str,
+            method: &'a str,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            params: Option<&'a Value>,
+            id: &'a RequestId,
+        }
+
+        match self {
+            JsonRpcMessage::Request(request) => Request {
+                jsonrpc: &request.jsonrpc,
+                method: &request.method,
+                params: request.params.as_ref(),
+                id: &request.id,
+            }
+            .serialize(serializer),
+            JsonRpcMessage::Notification(notification) => notification.serialize(serializer),
+            JsonRpcMessage::Response(response) | JsonRpcMessage::Error(response) => {
+                response.serialize(serializer)
+            }
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for JsonRpcMessage {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let value = Value::deserialize(deserializer)?;
+        if value.get("jsonrpc").and_then(Value::as_str) != Some("2.0") {
+            return Err(de::Error::custom("jsonrpc must be \"2.0\""));
+        }
+        let message = match (value.get("method"), value.get("id")) {
+            (Some(_), Some(_)) => serde_json::from_value(value).map(JsonRpcMessage::Request),
+            (Some(_), None) => serde_json::from_value(value).map(JsonRpcMessage::Notification),
+            (None, _) => serde_json::from_value(value).map(|response: JsonRpcResponse<Value>| {
+                match response.error {
+                    Some(_) => JsonRpcMessage::Error(response),
+                    None => JsonRpcMessage::Response(response),
+                }
+            }),
+        };
+        message.map_err(de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn round_trip(message: Value) -> JsonRpcMessage {
+        let parsed: JsonRpcMessage = serde_json::from_value(message.clone()).unwrap();
+        assert_eq!(serde_json::to_value(&parsed).unwrap(), message);
+        parsed
+    }
+
+    #[test]
+    fn numeric_ids_round_trip() {
+        let request = round_trip(json!({"jsonrpc": "2.0", "method": "ping", "id": 7}));
+        assert!(matches!(request, JsonRpcMessage::Request(r) if r.id == RequestId::Number(7)));
+
+        let response = round_trip(json!({"jsonrpc": "2.0", "result": {}, "id": -3}));
+        assert!(matches!(response, JsonRpcMessage::Response(r) if r.id == Some(RequestId::Number(-3))));
+    }
+
+    #[test]
+    fn string_ids_round_trip() {
+        let request = round_trip(json!({"jsonrpc": "2.0", "method": "ping", "id": "a-1"}));
+        let JsonRpcMessage::Request(request) = request else {
+            panic!("expected a request");
+        };
+        assert_eq!(request.id, RequestId::String("a-1".to_string()));
+        assert_eq!(request.id.to_string(), "\"a-1\"");
+
+        let response = round_trip(json!({"jsonrpc": "2.0", "result": 1, "id": "a-1"}));
+        assert!(matches!(response, JsonRpcMessage::Response(r) if r.id == Some("a-1".to_string().into())));
+    }
+
+    #[test]
+    fn error_responses_may_have_a_null_id() {
+        let message = json!({
+            "jsonrpc": "2.0",
+            "error": {"code": -32700, "message": "Parse error"},
+            "id": null,
+        });
+        let JsonRpcMessage::Error(response) = round_trip(message) else {
+            panic!("expected an error response");
+        };
+        assert_eq!(response.id, None);
+        assert_eq!(response.error.unwrap().code, -32700);
+    }
+
+    #[test]
+    fn responses_need_exactly_one_of_result_and_error() {
+        let both = json!({
+            "jsonrpc": "2.0",
+            "result": {},
+            "error": {"code": -32603, "message": "Internal error"},
+            "id": 1,
+        });
+        let neither = json!({"jsonrpc": "2.0", "id": 1});
+        for message in [both, neither] {
+            assert!(serde_json::from_value::<JsonRpcResponse<Value>>(message.clone()).is_err());
+            assert!(serde_json::from_value::<JsonRpcMessage>(message).is_err());
+        }
+
+        let response = JsonRpcResponse::<Value> {
+            jsonrpc: "2.0".to_string(),
+            result: None,
+            error: None,
+            id: Some(RequestId::Number(1)),
+        };
+        assert!(serde_json::to_string(&response).is_err());
+    }
+
+    #[test]
+    fn null_results_round_trip() {
+        let message = json!({"jsonrpc": "2.0", "result": null, "id": 1});
+        let response: JsonRpcResponse<Value> = serde_json::from_value(message.clone()).unwrap();
+        assert_eq!(response.result, Some(Value::Null));
+        assert_eq!(serde_json::to_value(&response).unwrap(), message);
+
+        let JsonRpcMessage::Response(response) = round_trip(message) else {
+            panic!("expected a response");
+        };
+        assert_eq!(response.result, Some(Value::Null));
+    }
+
+    #[test]
+    fn messages_need_jsonrpc_2_0() {
+        let message = json!({"jsonrpc": "1.0", "method": "ping", "id": 1});
+        assert!(serde_json::from_value::<JsonRpcMessage>(message).is_err());
+    }
 }
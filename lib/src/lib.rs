@@ -1,4 +1,11 @@
 pub mod client;
+pub mod error;
 pub mod jsonrpc;
+pub mod logging;
+pub mod protocol;
 pub mod server;
+pub mod transport;
 pub mod types;
+pub mod uri_template;
+
+pub use error::Error;
@@ -0,0 +1,136 @@
+use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast;
+
+/// Log severity levels from RFC 5424, least severe first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogLevel {
+    Debug,
+    Info,
+    Notice,
+    Warning,
+    Error,
+    Critical,
+    Alert,
+    Emergency,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SetLevelParams {
+    pub level: LogLevel,
+}
+
+/// Params of a `notifications/message` notification.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LoggingMessageParams {
+    pub level: LogLevel,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub logger: Option<String>,
+    pub data: serde_json::Value,
+}
+
+/// Messages that can be queued for a session before older ones are dropped.
+const LOG_CAPACITY: usize = 1024;
+
+/// Sends log messages to clients as `notifications/message`. Clone it into
+/// tools and anything else that logs; every server session using it forwards
+/// messages at or above the level its client asked for with `logging/setLevel`.
+#[derive(Clone)]
+pub struct Logger {
+    sender: broadcast::Sender<LoggingMessageParams>,
+    name: Option<String>,
+}
+
+impl Logger {
+    pub fn new() -> Self {
+        let (sender, _) = broadcast::channel(LOG_CAPACITY);
+        Logger { sender, name: None }
+    }
+
+    /// Returns a logger sending to the same clients under the given logger name.
+    pub fn named(&self, name: &str) -> Logger {
+        Logger {
+            sender: self.sender.clone(),
+            name: Some(name.to_string()),
+        }
+    }
+
+    /// Sends a message with arbitrary JSON data, usually a string or an object.
+    pub fn log(&self, level: LogLevel, data: impl Into<serde_json::Value>) {
+        // No receivers just means no sessions are running
+        let _ = self.sender.send(LoggingMessageParams {
+            level,
+            logger: self.name.clone(),
+            data: data.into(),
+        });
+    }
+
+    pub fn debug(&self, message: &str) {
+        self.log(LogLevel::Debug, message);
+    }
+
+    pub fn info(&self, message: &str) {
+        self.log(LogLevel::Info, message);
+    }
+
+    pub fn warning(&self, message: &str) {
+        self.log(LogLevel::Warning, message);
+    }
+
+    pub fn error(&self, message: &str) {
+        self.log(LogLevel::Error, message);
+    }
+
+    pub(crate) fn subscribe(&self) -> broadcast::Receiver<LoggingMessageParams> {
+        self.sender.subscribe()
+    }
+}
+
+impl Default for Logger {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Forwards records from the `log` crate to clients through a [`Logger`],
+/// using each record's target as the logger name. Records are also written to
+/// stderr, so local logs are unaffected.
+pub struct LogBridge {
+    logger: Logger,
+    level: log::LevelFilter,
+}
+
+impl LogBridge {
+    /// Installs the bridge as the global `log` logger. Fails if another logger
+    /// is already installed.
+    pub fn init(logger: Logger, level: log::LevelFilter) -> Result<(), log::SetLoggerError> {
+        log::set_boxed_logger(Box::new(LogBridge { logger, level }))?;
+        log::set_max_level(level);
+        Ok(())
+    }
+}
+
+impl log::Log for LogBridge {
+    fn enabled(&self, metadata: &log::Metadata) -> bool {
+        metadata.level() <= self.level
+    }
+
+    fn log(&self, record: &log::Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        let message = record.args().to_string();
+        eprintln!("[{}] {}: {}", record.level(), record.target(), message);
+        let level = match record.level() {
+            log::Level::Error => LogLevel::Error,
+            log::Level::Warn => LogLevel::Warning,
+            log::Level::Info => LogLevel::Info,
+            log::Level::Debug | log::Level::Trace => LogLevel::Debug,
+        };
+        self.logger.named(record.target()).log(level, message);
+    }
+
+    fn flush(&self) {}
+}
@@ -0,0 +1,23 @@
+/// The newest protocol version this crate speaks.
+pub const LATEST_PROTOCOL_VERSION: &str = "2025-06-18";
+
+/// Protocol versions this crate speaks, newest first.
+pub const SUPPORTED_PROTOCOL_VERSIONS: &[&str] = &["2025-06-18", "2025-03-26", "2024-11-05"];
+
+/// Picks the version a server answers `initialize` with: the one the client
+/// requested if the server supports it, otherwise the server's preferred one.
+/// The client then decides whether it can work with that version.
+pub fn negotiate(requested: &str, supported: &[String]) -> String {
+    if supported.iter().any(|version| version == requested) {
+        return requested.to_string();
+    }
+    supported
+        .first()
+        .cloned()
+        .unwrap_or_else(|| LATEST_PROTOCOL_VERSION.to_string())
+}
+
+/// JSON-RPC batches were added in 2025-03-26 and removed again in 2025-06-18.
+pub fn supports_batching(version: &str) -> bool {
+    version == "2025-03-26"
+}
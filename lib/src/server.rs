@@ -1,61 +1,112 @@
 use serde::{Deserialize, Serialize};
-use std::{
-    collections::HashMap,
-    io::{self, BufRead, BufReader, Read, Write},
-};
+use std::collections::{HashMap, HashSet};
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::mpsc::{UnboundedReceiver, UnboundedSender, unbounded_channel};
+use tokio::task::{JoinError, JoinSet};
+use tokio_util::sync::CancellationToken;
 
 use crate::{
+    Error,
     client::InitializeParams,
-    jsonrpc::{JsonRpcError, JsonRpcRequest, JsonRpcResponse},
-    types::{AddParams, Tool, ToolImplementation, ToolsCallParams, ToolsCallResult, ToolsListParams, ToolsListResult},
+    jsonrpc::{
+        JsonRpcError, JsonRpcMessage, JsonRpcNotification, JsonRpcRequest, JsonRpcResponse,
+        RequestId,
+    },
+    logging::{LogLevel, Logger, LoggingMessageParams, SetLevelParams},
+    protocol::{self, SUPPORTED_PROTOCOL_VERSIONS},
+    transport::{StdioTransport, Transport, TransportReceiver, TransportSender},
+    types::{
+        AddParams, AsyncToolImplementation, CancelledParams, Prompt, PromptImplementation, PromptsGetParams,
+        PromptsGetResult, PromptsListParams, PromptsListResult, ResourceChange, ResourceNotifier, ResourceProvider,
+        ResourceTemplatesListParams, ResourceTemplatesListResult, ResourceUpdatedParams,
+        ResourcesListParams, ResourcesListResult, ResourcesReadParams, ResourcesReadResult,
+        ResourcesSubscribeParams, ResourcesUnsubscribeParams, SyncTool, Tool, ToolContext, ToolImplementation,
+        ToolsCallParams, ToolsListParams, ToolsListResult,
+    },
+    uri_template::UriTemplate,
 };
 
-#[derive(Debug, Serialize, Deserialize)]
+/// Number of items returned per page by paginated list methods, unless overridden.
+pub const DEFAULT_PAGE_SIZE: usize = 100;
+
+/// Least severe log level sent to a client that hasn't called `logging/setLevel`.
+pub const DEFAULT_LOG_LEVEL: LogLevel = LogLevel::Info;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct ServerCapabilities {
+    // A capability is offered by being present, so absent ones are left out
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub logging: Option<ServerLoggingCapabilities>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub prompts: Option<ServerPromptsCapabilities>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub resources: Option<ServerResourcesCapabilities>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub tools: Option<ServerToolsCapabilities>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub experimental: Option<serde_json::Value>, // Use Value for flexibility
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+impl ServerCapabilities {
+    /// Returns false for methods that belong to a capability the server lacks.
+    /// Methods outside any capability, like `ping`, are always supported.
+    pub fn supports(&self, method: &str) -> bool {
+        match method {
+            "tools/list" | "tools/call" => self.tools.is_some(),
+            "resources/list" | "resources/templates/list" | "resources/read" => {
+                self.resources.is_some()
+            }
+            "resources/subscribe" | "resources/unsubscribe" => self
+                .resources
+                .as_ref()
+                .is_some_and(|resources| resources.subscribe == Some(true)),
+            "prompts/list" | "prompts/get" => self.prompts.is_some(),
+            "logging/setLevel" => self.logging.is_some(),
+            _ => true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct ServerLoggingCapabilities {} // Placeholder
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct ServerPromptsCapabilities {
     pub list_changed: Option<bool>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct ServerResourcesCapabilities {
     pub subscribe: Option<bool>,
     pub list_changed: Option<bool>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct ServerToolsCapabilities {
     pub list_changed: Option<bool>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct ServerInfo {
     pub name: String,
     pub version: String,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct InitializeResult {
     pub protocol_version: String,
     pub capabilities: ServerCapabilities,
     pub server_info: ServerInfo,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub instructions: Option<String>,
 }
 
@@ -67,132 +118,863 @@ pub enum ServerState {
 }
 
 pub struct Server {
-    pub reader: BufReader<io::StdinLock<'static>>,
-    pub writer: io::Stdout,
+    pub transport: Box<dyn Transport>,
     pub state: ServerState,
-    pub tools: HashMap<String, Box<dyn ToolImplementation>>,
+    /// Name and version reported to clients in the initialize response.
+    pub server_info: ServerInfo,
+    /// Hints for the client on how to use the server.
+    pub instructions: Option<String>,
+    /// Protocol versions the server speaks, preferred first. A client asking
+    /// for any other version is offered the first one.
+    pub protocol_versions: Vec<String>,
+    pub tools: HashMap<String, Arc<dyn AsyncToolImplementation>>,
+    pub resources: Vec<Arc<dyn ResourceProvider>>,
+    pub prompts: HashMap<String, Arc<dyn PromptImplementation>>,
+    /// Where providers report resource changes. Replace it with a shared
+    /// notifier to reach the sessions of several servers at once.
+    pub resource_notifier: ResourceNotifier,
+    /// Where server-wide log messages go, e.g. those of a
+    /// [`LogBridge`](crate::logging::LogBridge). Every session of every server
+    /// sharing it receives them; tools log to their own client through
+    /// [`ToolContext::logger`] instead.
+    pub logger: Logger,
+    /// Maximum number of items in one page of a paginated list.
+    pub page_size: usize,
+}
+
+impl Default for Server {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Server {
+    /// Starts configuring a server with the given name and version.
+    pub fn builder(name: &str, version: &str) -> ServerBuilder {
+        ServerBuilder::new(name, version)
+    }
+
     /// Creates a new Server instance reading from stdin and writing to stdout.
     pub fn new() -> Self {
-        let stdin = io::stdin();
-        let reader = BufReader::new(stdin.lock());
-        let writer = io::stdout();
+        Self::with_transport(Box::new(StdioTransport::new()))
+    }
 
+    /// Creates a new Server instance serving over the given transport.
+    pub fn with_transport(transport: Box<dyn Transport>) -> Self {
         Server {
-            reader,
-            writer,
+            transport,
             state: ServerState::Uninitialized,
+            server_info: ServerInfo {
+                name: "mcp-server".to_string(),
+                version: env!("CARGO_PKG_VERSION").to_string(),
+            },
+            instructions: None,
+            protocol_versions: SUPPORTED_PROTOCOL_VERSIONS.iter().map(|v| v.to_string()).collect(),
             tools: HashMap::new(),
+            resources: Vec::new(),
+            prompts: HashMap::new(),
+            resource_notifier: ResourceNotifier::new(),
+            logger: Logger::new(),
+            page_size: DEFAULT_PAGE_SIZE,
         }
     }
 
-    /// Register a tool implementation
+    /// Register a synchronous tool implementation. Its calls run on a blocking
+    /// thread so they don't stall the message loop.
     pub fn register_tool(&mut self, tool: Box<dyn ToolImplementation>) {
+        self.register_async_tool(Arc::new(SyncTool(Arc::from(tool))));
+    }
+
+    /// Register an asynchronous tool implementation
+    pub fn register_async_tool(&mut self, tool: Arc<dyn AsyncToolImplementation>) {
         let tool_def = tool.get_tool();
         self.tools.insert(tool_def.name.clone(), tool);
     }
 
-    /// Reads a single JSON-RPC message, processes it, and sends a response if applicable.
-    /// Returns Ok(false) if EOF is reached, Ok(true) otherwise.
-    pub fn handle_message(&mut self) -> Result<bool, Box<dyn std::error::Error>> {
-        // Read headers
-        let mut headers = HashMap::new();
-        loop {
-            let mut line = String::new();
-            let bytes_read = self.reader.read_line(&mut line)?;
-            if bytes_read == 0 {
-                return Ok(false); // Client disconnected
+    /// Register a resource provider. When providers overlap, the one registered
+    /// first serves reads.
+    pub fn register_resource_provider(&mut self, provider: Arc<dyn ResourceProvider>) {
+        self.resources.push(provider);
+    }
+
+    /// Register a prompt implementation
+    pub fn register_prompt(&mut self, prompt: Box<dyn PromptImplementation>) {
+        let prompt_def = prompt.get_prompt();
+        self.prompts.insert(prompt_def.name.clone(), Arc::from(prompt));
+    }
+
+    /// The capabilities to advertise, based on what is registered. Logging is
+    /// always available.
+    pub fn capabilities(&self) -> ServerCapabilities {
+        ServerCapabilities {
+            logging: Some(ServerLoggingCapabilities {}),
+            prompts: (!self.prompts.is_empty()).then_some(ServerPromptsCapabilities {
+                list_changed: Some(false),
+            }),
+            resources: (!self.resources.is_empty()).then_some(ServerResourcesCapabilities {
+                subscribe: Some(true),
+                list_changed: Some(true),
+            }),
+            tools: (!self.tools.is_empty()).then_some(ServerToolsCapabilities {
+                list_changed: Some(false),
+            }),
+            experimental: None,
+        }
+    }
+
+    /// Runs the message loop on a new tokio runtime until the client disconnects.
+    pub fn run(self) -> Result<(), Error> {
+        let runtime = tokio::runtime::Builder::new_multi_thread()
+            .enable_all()
+            .build()?;
+        runtime.block_on(self.serve())
+    }
+
+    /// Runs the message loop until the client disconnects.
+    ///
+    /// Messages are read in order, but requests are dispatched concurrently, so a
+    /// slow tool call doesn't block other requests. Responses are written by a
+    /// single writer, one whole message at a time, in the order they complete.
+    /// Requests still running when the client disconnects are cancelled.
+    pub async fn serve(self) -> Result<(), Error> {
+        eprintln!("Server: Starting message loop...");
+        let capabilities = self.capabilities();
+        let (sender, receiver) = self.transport.split();
+
+        // Transports block, so reading and writing happen on their own threads
+        let (incoming_sender, mut incoming) = unbounded_channel();
+        std::thread::spawn(move || read_messages(receiver, incoming_sender));
+        let (outgoing, outgoing_receiver) = unbounded_channel();
+        let writer = std::thread::spawn(move || write_messages(sender, outgoing_receiver));
+
+        let mut session = Session {
+            capabilities,
+            state: self.state,
+            server_info: self.server_info,
+            instructions: self.instructions,
+            protocol_versions: self.protocol_versions,
+            protocol_version: None,
+            tools: self.tools,
+            resources: self.resources,
+            prompts: self.prompts,
+            page_size: self.page_size.max(1),
+            subscriptions: Arc::default(),
+            log_level: DEFAULT_LOG_LEVEL,
+            logger: Logger::new(),
+            outgoing,
+            in_flight: JoinSet::new(),
+            cancellations: Arc::default(),
+        };
+        // Holding these keeps their channels open while the loop runs
+        let resource_notifier = self.resource_notifier;
+        let mut resource_changes = resource_notifier.subscribe();
+        let logger = self.logger;
+        let mut log_messages = logger.subscribe();
+        let mut session_log_messages = session.logger.subscribe();
+        let result = loop {
+            tokio::select! {
+                message = incoming.recv() => {
+                    let body = match message {
+                        Some(Ok(body)) => body,
+                        Some(Err(Error::InvalidUtf8(details))) => {
+                            if let Err(e) = session.parse_error(details) {
+                                break Err(e);
+                            }
+                            continue;
+                        }
+                        Some(Err(e)) => break Err(e),
+                        None => {
+                            eprintln!("Server: Client disconnected, shutting down.");
+                            break Ok(());
+                        }
+                    };
+                    if let Err(e) = session.handle_message(&body) {
+                        eprintln!("Server: Error handling message: {}", e);
+                        break Err(e);
+                    }
+                    session.reap_finished();
+                }
+                change = resource_changes.recv() => match change {
+                    Ok(change) => {
+                        if let Err(e) = session.resource_changed(change) {
+                            break Err(e);
+                        }
+                    }
+                    Err(RecvError::Lagged(missed)) => {
+                        eprintln!("Server: Dropped {} resource changes", missed);
+                    }
+                    Err(RecvError::Closed) => unreachable!("the notifier is held above"),
+                },
+                message = log_messages.recv() => {
+                    if let Err(e) = session.log_message(message) {
+                        break Err(e);
+                    }
+                }
+                message = session_log_messages.recv() => {
+                    if let Err(e) = session.log_message(message) {
+                        break Err(e);
+                    }
+                }
+            }
+        };
+
+        // Nobody will read what in-flight requests produce, so stop them first
+        for (_, cancellation) in session.cancellations.lock().unwrap().drain() {
+            cancellation.cancel();
+        }
+        while let Some(finished) = session.in_flight.join_next().await {
+            log_task_failure(finished);
+        }
+        drop(session);
+        let _ = writer.join();
+        result
+    }
+}
+
+/// Configures a [`Server`]: its identity, the protocol versions it accepts,
+/// its transport and what it offers. Serves over stdio unless a transport is set.
+pub struct ServerBuilder {
+    server_info: ServerInfo,
+    instructions: Option<String>,
+    protocol_versions: Vec<String>,
+    transport: Option<Box<dyn Transport>>,
+    tools: Vec<Arc<dyn AsyncToolImplementation>>,
+    resources: Vec<Arc<dyn ResourceProvider>>,
+    prompts: Vec<Box<dyn PromptImplementation>>,
+    resource_notifier: Option<ResourceNotifier>,
+    logger: Option<Logger>,
+    page_size: usize,
+}
+
+impl ServerBuilder {
+    pub fn new(name: &str, version: &str) -> Self {
+        ServerBuilder {
+            server_info: ServerInfo {
+                name: name.to_string(),
+                version: version.to_string(),
+            },
+            instructions: None,
+            protocol_versions: SUPPORTED_PROTOCOL_VERSIONS.iter().map(|v| v.to_string()).collect(),
+            transport: None,
+            tools: Vec::new(),
+            resources: Vec::new(),
+            prompts: Vec::new(),
+            resource_notifier: None,
+            logger: None,
+            page_size: DEFAULT_PAGE_SIZE,
+        }
+    }
+
+    pub fn instructions(mut self, instructions: &str) -> Self {
+        self.instructions = Some(instructions.to_string());
+        self
+    }
+
+    /// Replaces the protocol versions the server speaks, preferred first.
+    pub fn protocol_versions(mut self, versions: &[&str]) -> Self {
+        self.protocol_versions = versions.iter().map(|v| v.to_string()).collect();
+        self
+    }
+
+    pub fn transport(mut self, transport: Box<dyn Transport>) -> Self {
+        self.transport = Some(transport);
+        self
+    }
+
+    pub fn tool(mut self, tool: Box<dyn ToolImplementation>) -> Self {
+        self.tools.push(Arc::new(SyncTool(Arc::from(tool))));
+        self
+    }
+
+    pub fn async_tool(mut self, tool: Arc<dyn AsyncToolImplementation>) -> Self {
+        self.tools.push(tool);
+        self
+    }
+
+    pub fn resource_provider(mut self, provider: Arc<dyn ResourceProvider>) -> Self {
+        self.resources.push(provider);
+        self
+    }
+
+    pub fn prompt(mut self, prompt: Box<dyn PromptImplementation>) -> Self {
+        self.prompts.push(prompt);
+        self
+    }
+
+    /// Shares a resource notifier, e.g. with the tools that change resources.
+    pub fn resource_notifier(mut self, notifier: ResourceNotifier) -> Self {
+        self.resource_notifier = Some(notifier);
+        self
+    }
+
+    /// Shares a logger, e.g. one installed with [`crate::logging::LogBridge`].
+    pub fn logger(mut self, logger: Logger) -> Self {
+        self.logger = Some(logger);
+        self
+    }
+
+    pub fn page_size(mut self, page_size: usize) -> Self {
+        self.page_size = page_size;
+        self
+    }
+
+    pub fn build(self) -> Server {
+        let transport = self
+            .transport
+            .unwrap_or_else(|| Box::new(StdioTransport::new()));
+        let mut server = Server::with_transport(transport);
+        server.server_info = self.server_info;
+        server.instructions = self.instructions;
+        server.protocol_versions = self.protocol_versions;
+        server.page_size = self.page_size;
+        if let Some(notifier) = self.resource_notifier {
+            server.resource_notifier = notifier;
+        }
+        if let Some(logger) = self.logger {
+            server.logger = logger;
+        }
+        for tool in self.tools {
+            server.register_async_tool(tool);
+        }
+        for provider in self.resources {
+            server.register_resource_provider(provider);
+        }
+        for prompt in self.prompts {
+            server.register_prompt(prompt);
+        }
+        server
+    }
+}
+
+/// Forwards messages from the transport to the message loop until the peer closes.
+fn read_messages(
+    mut receiver: Box<dyn TransportReceiver>,
+    incoming: UnboundedSender<Result<String, Error>>,
+) {
+    loop {
+        let message = match receiver.receive() {
+            Ok(Some(body)) => Ok(body),
+            Ok(None) => return,
+            Err(e) => Err(Error::from(e)),
+        };
+        // A message that isn't valid UTF-8 was still read whole, so keep going
+        let failed = matches!(&message, Err(e) if !matches!(e, Error::InvalidUtf8(_)));
+        if incoming.send(message).is_err() || failed {
+            return;
+        }
+    }
+}
+
+/// Writes queued messages to the transport one at a time, so concurrent
+/// responses never interleave on the wire.
+fn write_messages(mut sender: Box<dyn TransportSender>, mut outgoing: UnboundedReceiver<String>) {
+    while let Some(message) = outgoing.blocking_recv() {
+        if let Err(e) = sender.send(&message) {
+            eprintln!("Server: Failed to send message: {}", e);
+            return;
+        }
+    }
+}
+
+fn log_task_failure(finished: Result<(), JoinError>) {
+    if let Err(e) = finished {
+        eprintln!("Server: Request handler failed: {}", e);
+    }
+}
+
+/// Queues a JSON-RPC response for the writer.
+fn send_response<T: Serialize>(
+    outgoing: &UnboundedSender<String>,
+    response: &JsonRpcResponse<T>,
+) -> Result<(), Error> {
+    let response_str = serde_json::to_string(response)?;
+    outgoing
+        .send(response_str)
+        .map_err(|_| Error::closed("Transport writer has stopped"))?;
+    Ok(())
+}
+
+/// Reads the id of a message that may not be valid, for answering it.
+fn read_id(message: &serde_json::Value) -> Option<RequestId> {
+    message
+        .get("id")
+        .and_then(|id| serde_json::from_value(id.clone()).ok())
+}
+
+/// Builds an Invalid Request error response.
+fn invalid_request(id: Option<RequestId>, message: String) -> JsonRpcResponse<serde_json::Value> {
+    JsonRpcResponse {
+        jsonrpc: "2.0".to_string(),
+        result: None,
+        error: Some(JsonRpcError {
+            code: -32600, // Invalid Request
+            message,
+            data: None,
+        }),
+        id,
+    }
+}
+
+/// Returns the page of `items` starting at `cursor`, and the cursor for the next
+/// page if there is one. Cursors are opaque to clients; here they are offsets.
+fn paginate<T>(
+    items: Vec<T>,
+    cursor: Option<&str>,
+    page_size: usize,
+) -> Result<(Vec<T>, Option<String>), JsonRpcError> {
+    let start = match cursor {
+        None => 0,
+        Some(cursor) => cursor
+            .parse::<usize>()
+            .ok()
+            .filter(|&start| start <= items.len())
+            .ok_or_else(|| JsonRpcError {
+                code: -32602, // Invalid params
+                message: format!("Invalid cursor: {}", cursor),
+                data: None,
+            })?,
+    };
+    let end = start.saturating_add(page_size).min(items.len());
+    let next_cursor = (end < items.len()).then(|| end.to_string());
+    let page = items.into_iter().skip(start).take(end - start).collect();
+    Ok((page, next_cursor))
+}
+
+/// Checks prompt arguments against the prompt's declared arguments.
+fn validate_prompt_arguments(
+    prompt: &Prompt,
+    arguments: &HashMap<String, String>,
+) -> Result<(), JsonRpcError> {
+    let invalid = |message| JsonRpcError {
+        code: -32602, // Invalid params
+        message,
+        data: None,
+    };
+    for declared in &prompt.arguments {
+        if declared.required && !arguments.contains_key(&declared.name) {
+            return Err(invalid(format!(
+                "Missing required argument '{}' for prompt '{}'",
+                declared.name, prompt.name
+            )));
+        }
+    }
+    for name in arguments.keys() {
+        if !prompt.arguments.iter().any(|declared| &declared.name == name) {
+            return Err(invalid(format!(
+                "Unknown argument '{}' for prompt '{}'",
+                name, prompt.name
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Finds the provider for a resource URI: the first that lists it, or failing
+/// that, the first with a matching template, along with the matched variables.
+async fn find_resource(
+    providers: &[Arc<dyn ResourceProvider>],
+    uri: &str,
+) -> Option<(Arc<dyn ResourceProvider>, HashMap<String, String>)> {
+    for provider in providers {
+        if provider.list().await.iter().any(|resource| resource.uri == uri) {
+            return Some((provider.clone(), HashMap::new()));
+        }
+    }
+    for provider in providers {
+        for template in provider.templates() {
+            match UriTemplate::parse(&template.uri_template) {
+                Ok(parsed) => {
+                    if let Some(variables) = parsed.matches(uri) {
+                        return Some((provider.clone(), variables));
+                    }
+                }
+                Err(e) => eprintln!("Server: Ignoring resource template: {}", e),
+            }
+        }
+    }
+    None
+}
+
+fn resource_not_found(uri: &str) -> JsonRpcError {
+    JsonRpcError {
+        code: -32002, // Resource not found
+        message: format!("Resource not found: {}", uri),
+        data: Some(serde_json::json!({ "uri": uri })),
+    }
+}
+
+/// Why a handler failed.
+enum HandlerError {
+    /// The params don't fit the method. The client is told, and the session goes on.
+    InvalidParams(serde_json::Error),
+    /// A response couldn't be sent, which ends the session.
+    Transport(Error),
+}
+
+impl From<serde_json::Error> for HandlerError {
+    fn from(e: serde_json::Error) -> Self {
+        HandlerError::InvalidParams(e)
+    }
+}
+
+impl From<Error> for HandlerError {
+    fn from(e: Error) -> Self {
+        HandlerError::Transport(e)
+    }
+}
+
+/// The state of one connection while the message loop runs.
+struct Session {
+    capabilities: ServerCapabilities,
+    state: ServerState,
+    server_info: ServerInfo,
+    instructions: Option<String>,
+    protocol_versions: Vec<String>,
+    /// Version agreed on in initialize.
+    protocol_version: Option<String>,
+    tools: HashMap<String, Arc<dyn AsyncToolImplementation>>,
+    resources: Vec<Arc<dyn ResourceProvider>>,
+    prompts: HashMap<String, Arc<dyn PromptImplementation>>,
+    page_size: usize,
+    /// URIs of resources the client asked to hear about.
+    subscriptions: Arc<Mutex<HashSet<String>>>,
+    /// Least severe log level the client wants to receive.
+    log_level: LogLevel,
+    /// Given to this session's tool calls, so their logs reach only this client.
+    logger: Logger,
+    outgoing: UnboundedSender<String>,
+    /// Requests dispatched to their own task and not yet answered.
+    in_flight: JoinSet<()>,
+    /// Tokens for cancelling those requests, by id.
+    cancellations: Arc<Mutex<HashMap<RequestId, CancellationToken>>>,
+}
+
+impl Session {
+    /// Collects requests that have already finished.
+    fn reap_finished(&mut self) {
+        while let Some(finished) = self.in_flight.try_join_next() {
+            log_task_failure(finished);
+        }
+    }
+
+    /// Tells the client about a resource change it is interested in.
+    fn resource_changed(&mut self, change: ResourceChange) -> Result<(), Error> {
+        if !matches!(self.state, ServerState::Initialized) {
+            return Ok(());
+        }
+        match change {
+            ResourceChange::Updated(uri) if self.subscriptions.lock().unwrap().contains(&uri) => self
+                .send_notification(
+                    "notifications/resources/updated",
+                    ResourceUpdatedParams { uri },
+                ),
+            ResourceChange::Updated(_) => Ok(()),
+            ResourceChange::ListChanged => self.send_notification(
+                "notifications/resources/list_changed",
+                serde_json::json!({}),
+            ),
+        }
+    }
+
+    /// Sends a log message to the client if it is severe enough.
+    fn log_message(
+        &mut self,
+        message: Result<LoggingMessageParams, RecvError>,
+    ) -> Result<(), Error> {
+        let message = match message {
+            Ok(message) => message,
+            Err(RecvError::Lagged(missed)) => {
+                eprintln!("Server: Dropped {} log messages", missed);
+                return Ok(());
             }
+            Err(RecvError::Closed) => unreachable!("loggers are held while the loop runs"),
+        };
+        if !matches!(self.state, ServerState::Initialized) || message.level < self.log_level {
+            return Ok(());
+        }
+        self.send_notification("notifications/message", message)
+    }
+
+    /// Queues a JSON-RPC notification for the client.
+    fn send_notification<P: Serialize>(
+        &self,
+        method: &str,
+        params: P,
+    ) -> Result<(), Error> {
+        let notification = JsonRpcNotification {
+            jsonrpc: "2.0".to_string(),
+            method: method.to_string(),
+            params: Some(params),
+        };
+        self.outgoing
+            .send(serde_json::to_string(&notification)?)
+            .map_err(|_| Error::closed("Transport writer has stopped"))?;
+        Ok(())
+    }
 
-            let line = line.trim();
-            if line.is_empty() {
-                break; // End of headers
+    /// Answers a request from its own task once the future made by `handler`
+    /// completes. The future is given a token that fires if the client cancels
+    /// the request; it is then dropped, and no response is sent.
+    fn spawn_request<T, F, H>(
+        &mut self,
+        responses: &UnboundedSender<String>,
+        method: &'static str,
+        id: RequestId,
+        handler: H,
+    ) where
+        T: Serialize + Send + 'static,
+        F: Future<Output = Result<T, JsonRpcError>> + Send + 'static,
+        H: FnOnce(CancellationToken) -> F,
+    {
+        let cancellation = CancellationToken::new();
+        let handler = handler(cancellation.clone());
+        let cancellations = self.cancellations.clone();
+        cancellations
+            .lock()
+            .unwrap()
+            .insert(id.clone(), cancellation.clone());
+        let outgoing = responses.clone();
+        self.in_flight.spawn(async move {
+            // Checked first, so a handler that notices the cancellation itself
+            // and returns doesn't get its result sent
+            let result = tokio::select! {
+                biased;
+                _ = cancellation.cancelled() => None,
+                result = handler => Some(result),
+            };
+            cancellations.lock().unwrap().remove(&id);
+            let (result, error) = match result {
+                Some(Ok(result)) => (Some(result), None),
+                Some(Err(error)) => (None, Some(error)),
+                None => {
+                    eprintln!("Server: {} request {} was cancelled", method, id);
+                    return;
+                }
+            };
+            let response = JsonRpcResponse {
+                jsonrpc: "2.0".to_string(),
+                result,
+                error,
+                id: Some(id),
+            };
+            if let Err(e) = send_response(&outgoing, &response) {
+                eprintln!("Server: Failed to send {} response: {}", method, e);
             }
+        });
+    }
 
-            if let Some((key, value)) = line.split_once(':') {
-                headers.insert(key.trim().to_lowercase(), value.trim().to_string());
+    /// Processes a JSON-RPC message or batch, sending responses if applicable.
+    /// Bad input is answered with an error; only failing to send ends the session.
+    fn handle_message(&mut self, body: &str) -> Result<(), Error> {
+        let raw_message = match serde_json::from_str(body) {
+            Ok(raw_message) => raw_message,
+            Err(e) => return self.parse_error(e.to_string()),
+        };
+        match raw_message {
+            serde_json::Value::Array(batch) => self.handle_batch(batch),
+            message => {
+                let responses = self.outgoing.clone();
+                self.handle_single(message, &responses)
             }
         }
+    }
 
-        // Safely extract Content-Length
-        let content_length = headers
-            .get("content-length")
-            .ok_or("Missing Content-Length header")?
-            .parse::<usize>()
-            .map_err(|_| "Invalid Content-Length")?;
+    /// Answers a message that couldn't be parsed. Its id is unknown, so the
+    /// error goes out with a null id.
+    fn parse_error(&self, details: String) -> Result<(), Error> {
+        let response = JsonRpcResponse::<serde_json::Value> {
+            jsonrpc: "2.0".to_string(),
+            result: None,
+            error: Some(JsonRpcError {
+                code: -32700, // Parse error
+                message: "Parse error".to_string(),
+                data: Some(serde_json::json!({ "details": details })),
+            }),
+            id: None,
+        };
+        send_response(&self.outgoing, &response)
+    }
 
-        // Read body
-        let mut body = vec![0; content_length];
-        self.reader.read_exact(&mut body)?;
+    /// Handles the members of a batch concurrently and answers with one array
+    /// holding their responses, in the order they complete. Notifications get
+    /// no response, so a batch of only notifications gets none at all.
+    fn handle_batch(&mut self, batch: Vec<serde_json::Value>) -> Result<(), Error> {
+        let version = self.protocol_version.as_deref().unwrap_or("(not initialized)");
+        if !protocol::supports_batching(version) {
+            let message = format!("Batch requests are not allowed in protocol version {}", version);
+            return send_response(&self.outgoing, &invalid_request(None, message));
+        }
+        if batch.is_empty() {
+            let message = "Batch must not be empty".to_string();
+            return send_response(&self.outgoing, &invalid_request(None, message));
+        }
 
-        // Attempt to deserialize as a generic JSON-RPC message to get method and id
-        let raw_message: serde_json::Value = serde_json::from_slice(&body)?;
-        let method = raw_message["method"].as_str().ok_or("Missing method")?;
-        let id = raw_message["id"].as_u64(); // id is optional for notifications
+        let (responses, mut collected) = unbounded_channel();
+        for message in batch {
+            // Each member is checked on its own, so one bad member doesn't spoil the rest
+            let result = if message["method"] == "initialize" {
+                let error = "initialize must not be part of a batch".to_string();
+                send_response(&responses, &invalid_request(read_id(&message), error))
+            } else {
+                self.handle_single(message, &responses)
+            };
+            if let Err(e) = result {
+                eprintln!("Server: Error handling batch member: {}", e);
+            }
+        }
+        drop(responses);
 
-        match (self.state, method) {
-            (ServerState::Uninitialized, "initialize") => {
-                eprintln!("Server: Received initialize request");
-                let request: JsonRpcRequest<InitializeParams> =
-                    serde_json::from_value(raw_message)?;
+        // Requests still running hold the sender until they have answered
+        let outgoing = self.outgoing.clone();
+        self.in_flight.spawn(async move {
+            let mut members = Vec::new();
+            while let Some(response) = collected.recv().await {
+                members.push(response);
+            }
+            if !members.is_empty() && outgoing.send(format!("[{}]", members.join(","))).is_err() {
+                eprintln!("Server: Failed to send batch response: Transport writer has stopped");
+            }
+        });
+        Ok(())
+    }
+
+    /// Processes a single JSON-RPC message, sending any response to `responses`.
+    /// Long-running requests are answered from their own task.
+    fn handle_single(
+        &mut self,
+        mut raw_message: serde_json::Value,
+        responses: &UnboundedSender<String>,
+    ) -> Result<(), Error> {
+        let message = match serde_json::from_value(raw_message.clone()) {
+            Ok(message) => message,
+            Err(e) => {
+                let error = format!("Invalid Request: {}", e);
+                return send_response(responses, &invalid_request(read_id(&raw_message), error));
+            }
+        };
+        let (method, id) = match message {
+            JsonRpcMessage::Request(request) => (request.method, Some(request.id)),
+            JsonRpcMessage::Notification(notification) => (notification.method, None),
+            JsonRpcMessage::Response(_) | JsonRpcMessage::Error(_) => {
+                // The server sends no requests, so nothing is waiting for this
+                eprintln!("Server: Ignoring unexpected response");
+                return Ok(());
+            }
+        };
 
-                // Basic version negotiation: only support the specified version
-                if request.params.protocol_version != "2025-03-26" {
+        // Params may be left out when all of a method's params are optional
+        if let Some(object) = raw_message.as_object_mut() {
+            object
+                .entry("params")
+                .or_insert_with(|| serde_json::json!({}));
+        }
+
+        match self.dispatch(&method, id.clone(), raw_message, responses) {
+            Ok(()) => Ok(()),
+            Err(HandlerError::InvalidParams(e)) => match id {
+                Some(id) => {
                     let response = JsonRpcResponse::<serde_json::Value> {
                         jsonrpc: "2.0".to_string(),
                         result: None,
                         error: Some(JsonRpcError {
                             code: -32602, // Invalid params
-                            message: "Unsupported protocol version".to_string(),
-                            // data: Some(serde_json::json!({ "supported": ["2025-03-26"], "requested": request.params.protocol_version })), // Optional data
+                            message: format!("Invalid params for {}", method),
+                            data: Some(serde_json::json!({ "details": e.to_string() })),
                         }),
-                        id: request.id,
+                        id: Some(id),
                     };
-                    self.send_response(&response)?;
-                    // Protocol error, maybe shut down? For now, just return true.
-                    return Ok(true);
+                    send_response(responses, &response)
+                }
+                None => {
+                    eprintln!("Server: Ignoring '{}' with invalid params: {}", method, e);
+                    Ok(())
+                }
+            },
+            Err(HandlerError::Transport(e)) => Err(e),
+        }
+    }
+
+    /// Runs the handler for `method` in the current state.
+    fn dispatch(
+        &mut self,
+        method: &str,
+        id: Option<RequestId>,
+        raw_message: serde_json::Value,
+        responses: &UnboundedSender<String>,
+    ) -> Result<(), HandlerError> {
+        match (self.state, method) {
+            (ServerState::Uninitialized, "initialize") => {
+                eprintln!("Server: Received initialize request");
+                let request: JsonRpcRequest<InitializeParams> =
+                    serde_json::from_value(raw_message)?;
+
+                // Answer with the requested version if we speak it, otherwise
+                // with our preferred one and let the client decide
+                let version =
+                    protocol::negotiate(&request.params.protocol_version, &self.protocol_versions);
+                if version != request.params.protocol_version {
+                    eprintln!(
+                        "Server: Client requested protocol version {}, offering {}",
+                        request.params.protocol_version, version
+                    );
                 }
+                self.protocol_version = Some(version.clone());
 
                 let response = JsonRpcResponse {
                     jsonrpc: "2.0".to_string(),
                     result: Some(InitializeResult {
-                        protocol_version: "2025-03-26".to_string(),
-                        capabilities: ServerCapabilities {
-                            logging: Some(ServerLoggingCapabilities {}),
-                            prompts: Some(ServerPromptsCapabilities {
-                                list_changed: Some(true),
-                            }),
-                            resources: Some(ServerResourcesCapabilities {
-                                subscribe: Some(true),
-                                list_changed: Some(true),
-                            }),
-                            tools: Some(ServerToolsCapabilities {
-                                list_changed: Some(true),
-                            }),
-                            experimental: None,
-                        },
-                        server_info: ServerInfo {
-                            name: "ExampleServer".to_string(),
-                            version: "1.0.0".to_string(),
-                        },
-                        instructions: Some(
-                            "Welcome! Send 'add' requests after initialization.".to_string(),
-                        ),
+                        protocol_version: version,
+                        capabilities: self.capabilities.clone(),
+                        server_info: self.server_info.clone(),
+                        instructions: self.instructions.clone(),
                     }),
                     error: None,
-                    id: request.id,
+                    id: Some(request.id),
                 };
-                self.send_response(&response)?;
+                send_response(responses, &response)?;
                 self.state = ServerState::Initializing; // Move to next state
-                Ok(true)
+                Ok(())
             }
             (ServerState::Initializing, "notifications/initialized") => {
                 eprintln!("Server: Received initialized notification");
                 // No params to deserialize for this notification
                 self.state = ServerState::Initialized; // Move to next state
-                Ok(true)
+                Ok(())
+            }
+            (ServerState::Initialized, method) if !self.capabilities.supports(method) => {
+                // Nothing is registered for this capability, so it isn't offered
+                if let Some(id) = id {
+                    let response = JsonRpcResponse::<serde_json::Value> {
+                        jsonrpc: "2.0".to_string(),
+                        result: None,
+                        error: Some(JsonRpcError {
+                            code: -32601, // Method not found
+                            message: format!("Method not found: '{}'", method),
+                            data: None,
+                        }),
+                        id: Some(id),
+                    };
+                    send_response(responses, &response)?;
+                }
+                Ok(())
+            }
+            (ServerState::Initialized, "notifications/cancelled") => {
+                let notification: JsonRpcNotification<CancelledParams> =
+                    serde_json::from_value(raw_message)?;
+                let Some(params) = notification.params else {
+                    return Ok(());
+                };
+                // Requests that already finished, or never existed, are ignored
+                let cancellation = self.cancellations.lock().unwrap().remove(&params.request_id);
+                if let Some(cancellation) = cancellation {
+                    eprintln!(
+                        "Server: Client cancelled request {} ({})",
+                        params.request_id,
+                        params.reason.as_deref().unwrap_or("no reason given")
+                    );
+                    cancellation.cancel();
+                }
+                Ok(())
             }
             (ServerState::Initialized, "add") => {
                 eprintln!("Server: Received add request");
@@ -203,10 +985,10 @@ impl Server {
                     jsonrpc: "2.0".to_string(),
                     result: Some(result),
                     error: None,
-                    id: request.id,
+                    id: Some(request.id),
                 };
-                self.send_response(&response)?;
-                Ok(true)
+                send_response(responses, &response)?;
+                Ok(())
             }
             (ServerState::Initialized, "tools/list") => {
                 eprintln!("Server: Received tools/list request");
@@ -224,36 +1006,229 @@ impl Server {
                         next_cursor: None, // No pagination for now
                     }),
                     error: None,
-                    id: request.id,
+                    id: Some(request.id),
                 };
-                self.send_response(&response)?;
-                Ok(true)
+                send_response(responses, &response)?;
+                Ok(())
             }
             (ServerState::Initialized, "tools/call") => {
                 eprintln!("Server: Received tools/call request");
                 let request: JsonRpcRequest<ToolsCallParams> = serde_json::from_value(raw_message)?;
 
-                let response = if let Some(tool_impl) = self.tools.get(&request.params.name) {
-                    let result = tool_impl.call(request.params.arguments);
-                    JsonRpcResponse {
-                        jsonrpc: "2.0".to_string(),
-                        result: Some(result),
-                        error: None,
-                        id: request.id,
-                    }
-                } else {
-                    JsonRpcResponse {
+                let Some(tool_impl) = self.tools.get(&request.params.name).cloned() else {
+                    let response = JsonRpcResponse::<serde_json::Value> {
                         jsonrpc: "2.0".to_string(),
                         result: None,
                         error: Some(JsonRpcError {
                             code: -32602, // Invalid params
                             message: format!("Unknown tool: {}", request.params.name),
+                            data: None,
                         }),
-                        id: request.id,
+                        id: Some(request.id),
+                    };
+                    send_response(responses, &response)?;
+                    return Ok(());
+                };
+
+                // Progress goes out right away, even for calls in a batch
+                let progress = request
+                    .params
+                    .meta
+                    .and_then(|meta| meta.progress_token)
+                    .map(|token| (token, self.outgoing.clone()));
+                let logger = self.logger.clone();
+
+                // The tool runs on its own task; its response goes out when it finishes
+                self.spawn_request(responses, "tools/call", request.id, |cancellation| async move {
+                    let context = ToolContext::new(cancellation, progress, logger);
+                    tool_impl.try_call(request.params.arguments, context).await
+                });
+                Ok(())
+            }
+            (ServerState::Initialized, "resources/list") => {
+                eprintln!("Server: Received resources/list request");
+                let request: JsonRpcRequest<ResourcesListParams> =
+                    serde_json::from_value(raw_message)?;
+
+                let providers = self.resources.clone();
+                let page_size = self.page_size;
+                self.spawn_request(responses, "resources/list", request.id, |_| async move {
+                    let mut resources = Vec::new();
+                    for provider in &providers {
+                        resources.extend(provider.list().await);
+                    }
+                    let (resources, next_cursor) =
+                        paginate(resources, request.params.cursor.as_deref(), page_size)?;
+                    Ok(ResourcesListResult {
+                        resources,
+                        next_cursor,
+                    })
+                });
+                Ok(())
+            }
+            (ServerState::Initialized, "resources/templates/list") => {
+                eprintln!("Server: Received resources/templates/list request");
+                let request: JsonRpcRequest<ResourceTemplatesListParams> =
+                    serde_json::from_value(raw_message)?;
+
+                let templates = self.resources.iter().flat_map(|p| p.templates()).collect();
+                let result = paginate(templates, request.params.cursor.as_deref(), self.page_size)
+                    .map(|(resource_templates, next_cursor)| ResourceTemplatesListResult {
+                        resource_templates,
+                        next_cursor,
+                    });
+                let response = JsonRpcResponse {
+                    jsonrpc: "2.0".to_string(),
+                    error: result.as_ref().err().cloned(),
+                    result: result.ok(),
+                    id: Some(request.id),
+                };
+                send_response(responses, &response)?;
+                Ok(())
+            }
+            (ServerState::Initialized, "resources/read") => {
+                eprintln!("Server: Received resources/read request");
+                let request: JsonRpcRequest<ResourcesReadParams> =
+                    serde_json::from_value(raw_message)?;
+
+                let providers = self.resources.clone();
+                self.spawn_request(responses, "resources/read", request.id, |_| async move {
+                    let uri = request.params.uri;
+                    let (provider, variables) =
+                        find_resource(&providers, &uri).await.ok_or_else(|| resource_not_found(&uri))?;
+                    let contents = provider
+                        .read(&uri, variables)
+                        .await
+                        .map_err(|message| JsonRpcError {
+                            code: -32603, // Internal error
+                            message,
+                            data: None,
+                        })?;
+                    Ok(ResourcesReadResult { contents })
+                });
+                Ok(())
+            }
+            (ServerState::Initialized, "prompts/list") => {
+                eprintln!("Server: Received prompts/list request");
+                let request: JsonRpcRequest<PromptsListParams> =
+                    serde_json::from_value(raw_message)?;
+
+                // Sorted, so pages stay stable between requests
+                let mut prompts: Vec<Prompt> =
+                    self.prompts.values().map(|p| p.get_prompt()).collect();
+                prompts.sort_by(|a, b| a.name.cmp(&b.name));
+                let result = paginate(prompts, request.params.cursor.as_deref(), self.page_size)
+                    .map(|(prompts, next_cursor)| PromptsListResult {
+                        prompts,
+                        next_cursor,
+                    });
+                let response = JsonRpcResponse {
+                    jsonrpc: "2.0".to_string(),
+                    error: result.as_ref().err().cloned(),
+                    result: result.ok(),
+                    id: Some(request.id),
+                };
+                send_response(responses, &response)?;
+                Ok(())
+            }
+            (ServerState::Initialized, "prompts/get") => {
+                eprintln!("Server: Received prompts/get request");
+                let request: JsonRpcRequest<PromptsGetParams> =
+                    serde_json::from_value(raw_message)?;
+
+                let result = match self.prompts.get(&request.params.name) {
+                    Some(prompt_impl) => {
+                        let prompt = prompt_impl.get_prompt();
+                        validate_prompt_arguments(&prompt, &request.params.arguments).and_then(
+                            |()| {
+                                let messages = prompt_impl
+                                    .render(request.params.arguments)
+                                    .map_err(|message| JsonRpcError {
+                                        code: -32603, // Internal error
+                                        message,
+                                        data: None,
+                                    })?;
+                                Ok(PromptsGetResult {
+                                    description: prompt.description,
+                                    messages,
+                                })
+                            },
+                        )
                     }
+                    None => Err(JsonRpcError {
+                        code: -32602, // Invalid params
+                        message: format!("Unknown prompt: {}", request.params.name),
+                        data: None,
+                    }),
+                };
+                let response = JsonRpcResponse {
+                    jsonrpc: "2.0".to_string(),
+                    error: result.as_ref().err().cloned(),
+                    result: result.ok(),
+                    id: Some(request.id),
                 };
-                self.send_response(&response)?;
-                Ok(true)
+                send_response(responses, &response)?;
+                Ok(())
+            }
+            (ServerState::Initialized, "logging/setLevel") => {
+                eprintln!("Server: Received logging/setLevel request");
+                let request: JsonRpcRequest<SetLevelParams> = serde_json::from_value(raw_message)?;
+
+                self.log_level = request.params.level;
+                let response = JsonRpcResponse {
+                    jsonrpc: "2.0".to_string(),
+                    result: Some(serde_json::json!({})),
+                    error: None,
+                    id: Some(request.id),
+                };
+                send_response(responses, &response)?;
+                Ok(())
+            }
+            (ServerState::Initialized, "resources/subscribe") => {
+                eprintln!("Server: Received resources/subscribe request");
+                let request: JsonRpcRequest<ResourcesSubscribeParams> =
+                    serde_json::from_value(raw_message)?;
+
+                // Subscribing to a URI nothing serves would never fire
+                let providers = self.resources.clone();
+                let subscriptions = self.subscriptions.clone();
+                self.spawn_request(responses, "resources/subscribe", request.id, |_| async move {
+                    let uri = request.params.uri;
+                    if find_resource(&providers, &uri).await.is_none() {
+                        return Err(resource_not_found(&uri));
+                    }
+                    subscriptions.lock().unwrap().insert(uri);
+                    Ok(serde_json::json!({}))
+                });
+                Ok(())
+            }
+            (ServerState::Initialized, "resources/unsubscribe") => {
+                eprintln!("Server: Received resources/unsubscribe request");
+                let request: JsonRpcRequest<ResourcesUnsubscribeParams> =
+                    serde_json::from_value(raw_message)?;
+
+                self.subscriptions.lock().unwrap().remove(&request.params.uri);
+                let response = JsonRpcResponse {
+                    jsonrpc: "2.0".to_string(),
+                    result: Some(serde_json::json!({})),
+                    error: None,
+                    id: Some(request.id),
+                };
+                send_response(responses, &response)?;
+                Ok(())
+            }
+            (_, "ping") => {
+                // Answered in any state, so clients can check liveness during long calls
+                if let Some(id) = id {
+                    let response = JsonRpcResponse {
+                        jsonrpc: "2.0".to_string(),
+                        result: Some(serde_json::json!({})),
+                        error: None,
+                        id: Some(id),
+                    };
+                    send_response(responses, &response)?;
+                }
+                Ok(())
             }
             (ServerState::Uninitialized, _) => {
                 // Received a request other than initialize before initialization
@@ -266,10 +1241,11 @@ impl Server {
                             message:
                                 "Server not initialized. 'initialize' must be the first request."
                                     .to_string(),
+                            data: None,
                         }),
-                        id,
+                        id: Some(id),
                     };
-                    self.send_response(&response)?;
+                    send_response(responses, &response)?;
                 } else {
                     // Received a notification before initialization, just ignore? Or log error?
                     eprintln!(
@@ -277,7 +1253,7 @@ impl Server {
                         method
                     );
                 }
-                Ok(true) // Continue processing
+                Ok(())
             }
             (ServerState::Initializing, _) => {
                 // Received any other message while in the Initializing state
@@ -291,17 +1267,18 @@ impl Server {
                                 "Server is initializing. Received unexpected method '{}'. Waiting for 'notifications/initialized'.",
                                 method
                             ),
+                            data: None,
                         }),
-                        id,
+                        id: Some(id),
                     };
-                    self.send_response(&response)?;
+                    send_response(responses, &response)?;
                 } else {
                     eprintln!(
                         "Server: Received unexpected notification '{}' while initializing. Ignoring.",
                         method
                     );
                 }
-                Ok(true) // Continue processing
+                Ok(())
             }
             (ServerState::Initialized, method) => {
                 // Received an unknown method after initialization
@@ -312,56 +1289,442 @@ impl Server {
                         error: Some(JsonRpcError {
                             code: -32601, // Method not found
                             message: format!("Method not found: '{}'", method),
+                            data: None,
                         }),
-                        id,
+                        id: Some(id),
                     };
-                    self.send_response(&response)?;
+                    send_response(responses, &response)?;
                 } else {
                     eprintln!(
                         "Server: Received unknown notification '{}'. Ignoring.",
                         method
                     );
                 }
-                Ok(true) // Continue processing
+                Ok(())
             }
         }
     }
+}
 
-    /// Sends a JSON-RPC response to the client.
-    pub fn send_response<T: serde::Serialize>(
-        &mut self,
-        response: &JsonRpcResponse<T>,
-    ) -> Result<(), Box<dyn std::error::Error>> {
-        let response_str = serde_json::to_string(response)?;
-        write!(
-            self.writer,
-            "Content-Length: {}\r\n\r\n{}",
-            response_str.len(),
-            response_str
-        )?;
-        self.writer.flush()?;
-        Ok(())
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::Client;
+    use crate::transport::MemoryTransport;
+    use crate::types::{
+        PromptArgument, PromptMessage, Resource, ResourceContents, ResourceTemplate, Role,
+        ToolsCallResult, prompt_message, success_content, text_resource,
+    };
+    use crate::transport::framing::{self, Framing};
+    use std::error::Error as StdError;
+    use std::io::Cursor;
+    use std::time::Duration;
+
+    /// Feeds the server fixed input bytes and records what it sends.
+    struct ScriptedTransport {
+        sender: Recorder,
+        receiver: Script,
     }
 
-    pub fn run(&mut self) -> Result<(), Box<dyn std::error::Error>> {
-        eprintln!("Server: Starting message loop...");
-        loop {
-            match self.handle_message() {
-                Ok(true) => {
-                    // Message handled, continue loop
-                }
-                Ok(false) => {
-                    // EOF detected, break loop
-                    eprintln!("Server: Client disconnected, shutting down.");
-                    return Ok(()); // Clean shutdown
-                }
-                Err(e) => {
-                    eprintln!("Server: Error handling message: {}", e);
-                    // Decide how to handle errors: return the error, or continue?
-                    // For now, let's return the error to stop the server.
-                    return Err(e);
-                }
+    struct Recorder(Arc<Mutex<Vec<serde_json::Value>>>);
+
+    /// Detects the framing from the first message when none is given, as
+    /// stdio does.
+    struct Script {
+        input: Cursor<Vec<u8>>,
+        framing: Option<Framing>,
+    }
+
+    impl TransportSender for Recorder {
+        fn send(&mut self, message: &str) -> Result<(), Box<dyn StdError>> {
+            self.0.lock().unwrap().push(serde_json::from_str(message)?);
+            Ok(())
+        }
+
+        fn close(&mut self) -> Result<(), Box<dyn StdError>> {
+            Ok(())
+        }
+    }
+
+    impl TransportReceiver for Script {
+        fn receive(&mut self) -> Result<Option<String>, Box<dyn StdError>> {
+            if let Some(framing) = self.framing {
+                return framing::read_message(&mut self.input, framing);
             }
+            let Some((message, framing)) = framing::read_message_detect(&mut self.input)? else {
+                return Ok(None);
+            };
+            self.framing = Some(framing);
+            Ok(Some(message))
+        }
+    }
+
+    impl Transport for ScriptedTransport {
+        fn send(&mut self, message: &str) -> Result<(), Box<dyn StdError>> {
+            self.sender.send(message)
+        }
+
+        fn receive(&mut self) -> Result<Option<String>, Box<dyn StdError>> {
+            self.receiver.receive()
         }
+
+        fn close(&mut self) -> Result<(), Box<dyn StdError>> {
+            self.sender.close()
+        }
+
+        fn split(self: Box<Self>) -> (Box<dyn TransportSender>, Box<dyn TransportReceiver>) {
+            (Box::new(self.sender), Box::new(self.receiver))
+        }
+    }
+
+    /// Runs a server over `input` until it ends and returns everything it sent.
+    fn run(input: Vec<u8>, framing: Option<Framing>) -> Vec<serde_json::Value> {
+        let sent = Arc::new(Mutex::new(Vec::new()));
+        let transport = ScriptedTransport {
+            sender: Recorder(sent.clone()),
+            receiver: Script {
+                input: Cursor::new(input),
+                framing,
+            },
+        };
+        Server::builder("test", "1.0")
+            .transport(Box::new(transport))
+            .build()
+            .run()
+            .unwrap();
+        Arc::try_unwrap(sent).unwrap().into_inner().unwrap()
+    }
+
+    fn frame(message: &[u8], framing: Framing) -> Vec<u8> {
+        match framing {
+            Framing::NewlineDelimited => [message, b"\n"].concat(),
+            Framing::ContentLength => {
+                let header = format!("Content-Length: {}\r\n\r\n", message.len());
+                [header.as_bytes(), message].concat()
+            }
+        }
+    }
+
+    fn keeps_serving_after_invalid_utf8(framing: Framing) {
+        let mut input = frame(b"{\"jsonrpc\":\"2.0\",\"method\":\"\xff\",\"id\":1}", framing);
+        input.extend(frame(br#"{"jsonrpc":"2.0","method":"ping","id":2}"#, framing));
+
+        let sent = run(input, Some(framing));
+        assert_eq!(sent.len(), 2, "{:?}", sent);
+        assert_eq!(sent[0]["error"]["code"], -32700);
+        assert_eq!(sent[0]["id"], serde_json::Value::Null);
+        assert_eq!(sent[1]["id"], 2);
+        assert!(sent[1].get("result").is_some());
+    }
+
+    #[test]
+    fn newline_delimited_invalid_utf8_is_a_parse_error() {
+        keeps_serving_after_invalid_utf8(Framing::NewlineDelimited);
+    }
+
+    #[test]
+    fn content_length_invalid_utf8_is_a_parse_error() {
+        keeps_serving_after_invalid_utf8(Framing::ContentLength);
+    }
+
+    #[test]
+    fn a_malformed_first_line_is_a_parse_error_in_newline_delimited_mode() {
+        let mut input = b"hello\n".to_vec();
+        input.extend(frame(br#"{"jsonrpc":"2.0","method":"ping","id":2}"#, Framing::NewlineDelimited));
+
+        let sent = run(input, None);
+        assert_eq!(sent.len(), 2, "{:?}", sent);
+        assert_eq!(sent[0]["error"]["code"], -32700);
+        assert_eq!(sent[1]["id"], 2);
+        assert!(sent[1].get("result").is_some());
+    }
+
+    /// Only finishes once cancelled.
+    struct UntilCancelled;
+
+    #[async_trait::async_trait]
+    impl AsyncToolImplementation for UntilCancelled {
+        fn get_tool(&self) -> Tool {
+            Tool {
+                name: "wait".to_string(),
+                description: "Waits until cancelled".to_string(),
+                input_schema: serde_json::json!({ "type": "object" }),
+            }
+        }
+
+        async fn call(
+            &self,
+            _arguments: serde_json::Value,
+            context: ToolContext,
+        ) -> ToolsCallResult {
+            context.cancelled().await;
+            success_content(Vec::new())
+        }
+    }
+
+    /// Ignores cancellation and answers after a short while.
+    struct Stubborn;
+
+    #[async_trait::async_trait]
+    impl AsyncToolImplementation for Stubborn {
+        fn get_tool(&self) -> Tool {
+            Tool {
+                name: "stubborn".to_string(),
+                description: "Answers late, even when cancelled".to_string(),
+                input_schema: serde_json::json!({ "type": "object" }),
+            }
+        }
+
+        async fn call(
+            &self,
+            _arguments: serde_json::Value,
+            _context: ToolContext,
+        ) -> ToolsCallResult {
+            tokio::time::sleep(Duration::from_millis(200)).await;
+            success_content(Vec::new())
+        }
+    }
+
+    /// Serves the built server over a memory transport and initializes it by
+    /// hand, returning the client's end.
+    fn start_raw(
+        builder: ServerBuilder,
+    ) -> (MemoryTransport, tokio::task::JoinHandle<Result<(), Error>>) {
+        let (mut client_end, server_end) = MemoryTransport::pair();
+        let serving = tokio::spawn(builder.transport(Box::new(server_end)).build().serve());
+        let initialize = serde_json::json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "initialize",
+            "params": {
+                "protocolVersion": "2025-06-18",
+                "capabilities": {},
+                "clientInfo": { "name": "test", "version": "1.0" },
+            },
+        });
+        client_end.send(&initialize.to_string()).unwrap();
+        client_end.send(r#"{"jsonrpc":"2.0","method":"notifications/initialized"}"#).unwrap();
+        (client_end, serving)
+    }
+
+    fn call_tool(id: i64, name: &str) -> String {
+        serde_json::json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "method": "tools/call",
+            "params": { "name": name, "arguments": {} },
+        })
+        .to_string()
+    }
+
+    #[tokio::test]
+    async fn disconnecting_cancels_requests_in_flight() {
+        let builder = Server::builder("test", "1.0").async_tool(Arc::new(UntilCancelled));
+        let (mut client_end, serving) = start_raw(builder);
+        client_end.send(&call_tool(2, "wait")).unwrap();
+        // Hang up while the call runs, still taking whatever the server sends
+        client_end.close().unwrap();
+
+        tokio::time::timeout(Duration::from_secs(5), serving)
+            .await
+            .expect("server kept running after the client left")
+            .unwrap()
+            .unwrap();
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn cancelled_requests_are_never_answered() {
+        let builder = Server::builder("test", "1.0").async_tool(Arc::new(Stubborn));
+        let (mut client_end, _serving) = start_raw(builder);
+        client_end.send(&call_tool(2, "stubborn")).unwrap();
+        let cancel = serde_json::json!({
+            "jsonrpc": "2.0",
+            "method": "notifications/cancelled",
+            "params": { "requestId": 2 },
+        });
+        client_end.send(&cancel.to_string()).unwrap();
+
+        // Ask for something else once the tool would have answered
+        tokio::time::sleep(Duration::from_millis(400)).await;
+        client_end.send(r#"{"jsonrpc":"2.0","id":3,"method":"ping"}"#).unwrap();
+        let mut ids = Vec::new();
+        while ids.last() != Some(&serde_json::json!(3)) {
+            let message = client_end.receive().unwrap().unwrap();
+            let message: serde_json::Value = serde_json::from_str(&message).unwrap();
+            ids.push(message["id"].clone());
+        }
+        assert_eq!(ids, [1, 3]);
+    }
+
+    /// Lists `number://1` to `number://3`, and reads any `number://{n}`.
+    struct Numbers;
+
+    #[async_trait::async_trait]
+    impl ResourceProvider for Numbers {
+        async fn list(&self) -> Vec<Resource> {
+            (1..=3)
+                .map(|n| Resource {
+                    uri: format!("number://{}", n),
+                    name: n.to_string(),
+                    description: None,
+                    mime_type: None,
+                })
+                .collect()
+        }
+
+        fn templates(&self) -> Vec<ResourceTemplate> {
+            vec![ResourceTemplate {
+                uri_template: "number://{n}".to_string(),
+                name: "Number".to_string(),
+                description: None,
+                mime_type: None,
+            }]
+        }
+
+        async fn read(
+            &self,
+            uri: &str,
+            _variables: HashMap<String, String>,
+        ) -> Result<Vec<ResourceContents>, String> {
+            let n = uri.trim_start_matches("number://").to_string();
+            Ok(vec![text_resource(uri, None, n)])
+        }
+    }
+
+    /// Greets `name`, optionally in a given `style`.
+    struct Greeting;
+
+    impl PromptImplementation for Greeting {
+        fn get_prompt(&self) -> Prompt {
+            let argument = |name: &str, required| PromptArgument {
+                name: name.to_string(),
+                description: None,
+                required,
+            };
+            Prompt {
+                name: "greeting".to_string(),
+                description: None,
+                arguments: vec![argument("name", true), argument("style", false)],
+            }
+        }
+
+        fn render(&self, arguments: HashMap<String, String>) -> Result<Vec<PromptMessage>, String> {
+            let style = arguments.get("style").map_or("plainly", String::as_str);
+            let text = format!("Greet {} {}", arguments["name"], style);
+            Ok(vec![prompt_message(Role::User, text)])
+        }
+    }
+
+    /// Serves the built server over a memory transport and connects a client.
+    async fn connect(builder: ServerBuilder) -> Client {
+        let (client_end, server_end) = MemoryTransport::pair();
+        tokio::spawn(builder.transport(Box::new(server_end)).build().serve());
+        Client::connect(Box::new(client_end)).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn resources_are_listed_a_page_at_a_time() {
+        let builder = Server::builder("test", "1.0")
+            .resource_provider(Arc::new(Numbers))
+            .page_size(2);
+        let client = connect(builder).await;
+
+        let first = client.list_resources(None).await.unwrap();
+        let uris: Vec<&str> = first.resources.iter().map(|r| r.uri.as_str()).collect();
+        assert_eq!(uris, ["number://1", "number://2"]);
+        let second = client.list_resources(first.next_cursor).await.unwrap();
+        let uris: Vec<&str> = second.resources.iter().map(|r| r.uri.as_str()).collect();
+        assert_eq!(uris, ["number://3"]);
+        assert_eq!(second.next_cursor, None);
+
+        for cursor in ["not-a-cursor", "-1", "4"] {
+            let error = client.list_resources(Some(cursor.to_string())).await.unwrap_err();
+            assert_eq!(error.code(), Some(-32602), "{}", cursor);
+        }
+    }
+
+    #[tokio::test]
+    async fn subscribers_get_updates_until_they_unsubscribe() {
+        let notifier = ResourceNotifier::new();
+        let builder = Server::builder("test", "1.0")
+            .resource_provider(Arc::new(Numbers))
+            .resource_notifier(notifier.clone());
+        let client = connect(builder).await;
+        let mut updates = client.resource_updates();
+
+        let error = client.subscribe_resource("nothing://1".to_string()).await.unwrap_err();
+        assert_eq!(error.code(), Some(-32002));
+
+        client.subscribe_resource("number://1".to_string()).await.unwrap();
+        notifier.notify_updated("number://1");
+        assert_eq!(updates.recv().await.as_deref(), Some("number://1"));
+
+        client.unsubscribe_resource("number://1".to_string()).await.unwrap();
+        notifier.notify_updated("number://1");
+        client.subscribe_resource("number://2".to_string()).await.unwrap();
+        notifier.notify_updated("number://2");
+        assert_eq!(updates.recv().await.as_deref(), Some("number://2"));
+    }
+
+    #[tokio::test]
+    async fn prompts_reject_missing_and_unknown_arguments() {
+        let client = connect(Server::builder("test", "1.0").prompt(Box::new(Greeting))).await;
+        let get = |arguments: &[(&str, &str)]| {
+            let arguments = arguments
+                .iter()
+                .map(|&(name, value)| (name.to_string(), value.to_string()))
+                .collect();
+            client.get_prompt("greeting".to_string(), arguments)
+        };
+
+        let result = get(&[("name", "Ada")]).await.unwrap();
+        assert_eq!(result.messages[0].content.content["text"], "Greet Ada plainly");
+
+        for arguments in [&[("style", "warmly")][..], &[("name", "Ada"), ("tone", "dry")]] {
+            let error = get(arguments).await.unwrap_err();
+            assert_eq!(error.code(), Some(-32602), "{:?}", arguments);
+        }
+    }
+
+    #[tokio::test]
+    async fn capabilities_follow_what_is_registered() {
+        let client = connect(Server::builder("test", "1.0")).await;
+        let capabilities = client.server_capabilities().unwrap();
+        assert!(capabilities.logging.is_some());
+        assert!(capabilities.tools.is_none());
+        assert!(capabilities.resources.is_none());
+        assert!(capabilities.prompts.is_none());
+        assert!(!client.supports("tools/list"));
+
+        let builder = Server::builder("test", "1.0")
+            .prompt(Box::new(Greeting))
+            .resource_provider(Arc::new(Numbers));
+        let client = connect(builder).await;
+        let capabilities = client.server_capabilities().unwrap();
+        assert!(capabilities.tools.is_none());
+        assert_eq!(capabilities.resources.as_ref().unwrap().subscribe, Some(true));
+        assert!(capabilities.prompts.is_some());
+        assert!(client.supports("prompts/get"));
+        assert!(client.supports("resources/subscribe"));
+        assert!(!client.supports("tools/call"));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn initialize_reports_the_built_identity_and_instructions() {
+        let builder = Server::builder("weather", "2.3.1").instructions("Ask about the weather");
+        let (mut client_end, _serving) = start_raw(builder);
+        let response: serde_json::Value =
+            serde_json::from_str(&client_end.receive().unwrap().unwrap()).unwrap();
+        assert_eq!(
+            response["result"]["serverInfo"],
+            serde_json::json!({ "name": "weather", "version": "2.3.1" })
+        );
+        assert_eq!(response["result"]["instructions"], "Ask about the weather");
+
+        let (mut client_end, _serving) = start_raw(Server::builder("quiet", "1.0"));
+        let response: serde_json::Value =
+            serde_json::from_str(&client_end.receive().unwrap().unwrap()).unwrap();
+        assert_eq!(response["result"]["serverInfo"]["name"], "quiet");
+        assert!(response["result"].get("instructions").is_none());
     }
 }
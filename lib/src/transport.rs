@@ -0,0 +1,49 @@
+use std::error::Error;
+
+pub mod framing;
+pub mod http_client;
+pub mod http_server;
+pub mod memory;
+pub mod sse;
+pub mod sse_client;
+pub mod sse_server;
+pub mod stdio;
+
+pub use framing::Framing;
+pub use http_client::StreamableHttpClientTransport;
+pub use memory::MemoryTransport;
+pub use sse_client::LegacySseClientTransport;
+pub use stdio::{ChildStdioTransport, StdioTransport};
+
+/// A bidirectional channel carrying serialized JSON-RPC messages.
+///
+/// Implementations own the framing of messages on the wire; callers only see
+/// complete message bodies.
+pub trait Transport: Send {
+    /// Sends a single serialized JSON-RPC message.
+    fn send(&mut self, message: &str) -> Result<(), Box<dyn Error>>;
+
+    /// Receives the next message. Returns Ok(None) once the peer has closed the connection.
+    fn receive(&mut self) -> Result<Option<String>, Box<dyn Error>>;
+
+    /// Closes the transport. Further sends fail, and the peer observes end of stream.
+    fn close(&mut self) -> Result<(), Box<dyn Error>>;
+
+    /// Splits the transport into halves that can be used from different threads,
+    /// so one can block receiving while the other sends.
+    fn split(self: Box<Self>) -> (Box<dyn TransportSender>, Box<dyn TransportReceiver>);
+}
+
+/// The sending half of a split [`Transport`].
+pub trait TransportSender: Send {
+    fn send(&mut self, message: &str) -> Result<(), Box<dyn Error>>;
+
+    /// Closes the transport; the receiving half observes end of stream once
+    /// the peer goes away.
+    fn close(&mut self) -> Result<(), Box<dyn Error>>;
+}
+
+/// The receiving half of a split [`Transport`].
+pub trait TransportReceiver: Send {
+    fn receive(&mut self) -> Result<Option<String>, Box<dyn Error>>;
+}
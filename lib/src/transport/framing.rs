@@ -0,0 +1,227 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::error::Error;
+use std::io::{BufRead, Write};
+
+use crate::Error as McpError;
+
+/// How messages are delimited on a byte stream.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Framing {
+    /// One JSON message per line, as specified by the MCP stdio transport.
+    #[default]
+    NewlineDelimited,
+    /// LSP-style `Content-Length` headers, kept for older peers.
+    ContentLength,
+}
+
+/// Writes a single message using the given framing.
+pub fn write_message(
+    writer: &mut impl Write,
+    message: &str,
+    framing: Framing,
+) -> Result<(), Box<dyn Error>> {
+    match framing {
+        Framing::NewlineDelimited => {
+            // Raw line breaks can only be insignificant whitespace in valid JSON
+            // (inside strings they are escaped), so they are safe to flatten.
+            if message.contains(['\n', '\r']) {
+                writeln!(writer, "{}", message.replace(['\n', '\r'], " "))?;
+            } else {
+                writeln!(writer, "{}", message)?;
+            }
+        }
+        Framing::ContentLength => {
+            write!(writer, "Content-Length: {}\r\n\r\n{}", message.len(), message)?;
+        }
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+/// Reads a single message using the given framing.
+/// Returns Ok(None) if the stream ends before a new message starts.
+pub fn read_message(
+    reader: &mut impl BufRead,
+    framing: Framing,
+) -> Result<Option<String>, Box<dyn Error>> {
+    match framing {
+        Framing::NewlineDelimited => read_line_message(reader),
+        Framing::ContentLength => read_content_length_message(reader),
+    }
+}
+
+/// Reads a single message, detecting the framing from its first line: a
+/// `Content-Length:` header starts an LSP-style message, and anything else is
+/// taken to be a line of JSON, even when it isn't valid JSON, so the peer gets a
+/// parse error rather than a stream stuck waiting for a body.
+/// Returns the message together with the framing it was sent with.
+pub fn read_message_detect(
+    reader: &mut impl BufRead,
+) -> Result<Option<(String, Framing)>, Box<dyn Error>> {
+    loop {
+        let mut line = Vec::new();
+        let bytes_read = reader.read_until(b'\n', &mut line)?;
+        if bytes_read == 0 {
+            return Ok(None); // Peer disconnected
+        }
+
+        // Skip blank lines between messages
+        let line = line.trim_ascii();
+        if line.is_empty() {
+            continue;
+        }
+        let is_content_length = line
+            .get(..CONTENT_LENGTH.len())
+            .is_some_and(|name| name.eq_ignore_ascii_case(CONTENT_LENGTH.as_bytes()));
+        if is_content_length {
+            let mut headers = HashMap::new();
+            insert_header(&mut headers, &String::from_utf8_lossy(line));
+            return Ok(read_content_length_body(reader, headers)?
+                .map(|m| (m, Framing::ContentLength)));
+        }
+        return match std::str::from_utf8(line) {
+            Ok(line) => Ok(Some((line.to_string(), Framing::NewlineDelimited))),
+            Err(e) => Err(McpError::InvalidUtf8(e.to_string()).into()),
+        };
+    }
+}
+
+fn read_line_message(reader: &mut impl BufRead) -> Result<Option<String>, Box<dyn Error>> {
+    loop {
+        let mut line = Vec::new();
+        let bytes_read = reader.read_until(b'\n', &mut line)?;
+        if bytes_read == 0 {
+            return Ok(None); // Peer disconnected
+        }
+
+        let line = line.trim_ascii();
+        if !line.is_empty() {
+            // The whole line was consumed, so a bad one doesn't desync the stream
+            return match std::str::from_utf8(line) {
+                Ok(line) => Ok(Some(line.to_string())),
+                Err(e) => Err(McpError::InvalidUtf8(e.to_string()).into()),
+            };
+        }
+    }
+}
+
+/// The header that starts every `Content-Length` framed message.
+const CONTENT_LENGTH: &str = "Content-Length:";
+
+fn read_content_length_message(
+    reader: &mut impl BufRead,
+) -> Result<Option<String>, Box<dyn Error>> {
+    read_content_length_body(reader, HashMap::new())
+}
+
+/// Reads the rest of a `Content-Length` framed message, given the headers
+/// already read.
+fn read_content_length_body(
+    reader: &mut impl BufRead,
+    mut headers: HashMap<String, String>,
+) -> Result<Option<String>, Box<dyn Error>> {
+    // Read headers
+    loop {
+        let mut line = String::new();
+        let bytes_read = reader.read_line(&mut line)?;
+        if bytes_read == 0 {
+            return Ok(None); // Peer disconnected
+        }
+
+        let line = line.trim();
+        if line.is_empty() {
+            break; // End of headers
+        }
+
+        insert_header(&mut headers, line);
+    }
+
+    // Safely extract Content-Length
+    let content_length = headers
+        .get("content-length")
+        .ok_or_else(|| McpError::Framing("Missing Content-Length header".to_string()))?
+        .parse::<usize>()
+        .map_err(|_| McpError::Framing("Invalid Content-Length".to_string()))?;
+
+    // Read body
+    let mut body = vec![0; content_length];
+    reader.read_exact(&mut body)?;
+    // The body was read whole, so the next message can still be found
+    let body = String::from_utf8(body).map_err(|e| McpError::InvalidUtf8(e.to_string()))?;
+    Ok(Some(body))
+}
+
+fn insert_header(headers: &mut HashMap<String, String>, line: &str) {
+    if let Some((key, value)) = line.split_once(':') {
+        headers.insert(key.trim().to_lowercase(), value.trim().to_string());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    fn assert_invalid_utf8(result: Result<Option<String>, Box<dyn Error>>) {
+        match result.map_err(McpError::from) {
+            Err(McpError::InvalidUtf8(_)) => {}
+            other => panic!("expected an invalid UTF-8 error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn newline_delimited_recovers_after_invalid_utf8() {
+        let mut reader = Cursor::new(b"{\"a\":\"\xff\xfe\"}\n{\"b\":1}\n".to_vec());
+        let framing = Framing::NewlineDelimited;
+        assert_invalid_utf8(read_message(&mut reader, framing));
+        assert_eq!(read_message(&mut reader, framing).unwrap().as_deref(), Some("{\"b\":1}"));
+        assert_eq!(read_message(&mut reader, framing).unwrap(), None);
+    }
+
+    #[test]
+    fn content_length_recovers_after_invalid_utf8() {
+        let mut input = b"Content-Length: 2\r\n\r\n\xff\xfe".to_vec();
+        input.extend_from_slice(b"Content-Length: 7\r\n\r\n{\"b\":1}");
+        let mut reader = Cursor::new(input);
+        let framing = Framing::ContentLength;
+        assert_invalid_utf8(read_message(&mut reader, framing));
+        assert_eq!(read_message(&mut reader, framing).unwrap().as_deref(), Some("{\"b\":1}"));
+        assert_eq!(read_message(&mut reader, framing).unwrap(), None);
+    }
+
+    #[test]
+    fn detected_framing_recovers_after_invalid_utf8() {
+        let mut reader = Cursor::new(b"{\xff}\n{\"b\":1}\n".to_vec());
+        let result = read_message_detect(&mut reader).map(|m| m.map(|(message, _)| message));
+        assert_invalid_utf8(result);
+        let (message, framing) = read_message_detect(&mut reader).unwrap().unwrap();
+        assert_eq!((message.as_str(), framing), ("{\"b\":1}", Framing::NewlineDelimited));
+    }
+
+    #[test]
+    fn detection_needs_a_content_length_header() {
+        let mut reader = Cursor::new(b"hello\n{\"b\":1}\n".to_vec());
+        let (message, framing) = read_message_detect(&mut reader).unwrap().unwrap();
+        assert_eq!((message.as_str(), framing), ("hello", Framing::NewlineDelimited));
+        assert_eq!(
+            read_message(&mut reader, framing).unwrap().as_deref(),
+            Some("{\"b\":1}")
+        );
+
+        let mut reader = Cursor::new(b"\r\ncontent-length: 7\r\n\r\n{\"b\":1}".to_vec());
+        let (message, framing) = read_message_detect(&mut reader).unwrap().unwrap();
+        assert_eq!((message.as_str(), framing), ("{\"b\":1}", Framing::ContentLength));
+        assert_eq!(read_message_detect(&mut reader).unwrap(), None);
+    }
+
+    #[test]
+    fn bad_content_length_is_a_framing_error() {
+        let mut reader = Cursor::new(b"Content-Length: x\r\n\r\n{}".to_vec());
+        match read_message(&mut reader, Framing::ContentLength).map_err(McpError::from) {
+            Err(McpError::Framing(_)) => {}
+            other => panic!("expected a framing error, got {:?}", other),
+        }
+    }
+}
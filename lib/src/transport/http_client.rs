@@ -0,0 +1,502 @@
+use reqwest::StatusCode;
+use reqwest::header::{ACCEPT, CONTENT_TYPE, HeaderMap};
+use std::collections::HashSet;
+use std::error::Error;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::runtime::Runtime;
+use tokio::sync::mpsc::{UnboundedReceiver, UnboundedSender, unbounded_channel};
+use tokio::task::JoinHandle;
+
+use super::{Transport, TransportReceiver, TransportSender};
+use super::http_server::{LAST_EVENT_ID_HEADER, PROTOCOL_VERSION_HEADER, SESSION_ID_HEADER};
+use super::sse::SseParser;
+
+/// Reconnection delay used until the server sends a `retry` field.
+const DEFAULT_RETRY: Duration = Duration::from_secs(1);
+
+/// Consecutive failed reconnections after which a stream is abandoned.
+const MAX_RESUME_ATTEMPTS: u32 = 5;
+
+/// Connects to an MCP server over the Streamable HTTP transport.
+///
+/// Each sent message is POSTed to the endpoint; responses arrive either as a
+/// JSON body or an SSE stream and are queued for `receive`. Once a session is
+/// established a GET stream is opened for server-initiated messages. Dropped
+/// SSE streams are resumed with `Last-Event-ID`.
+pub struct StreamableHttpClientTransport {
+    pub sender: StreamableHttpSender,
+    pub receiver: HttpReceiver,
+}
+
+/// POSTs messages and owns the background tasks reading response streams.
+pub struct StreamableHttpSender {
+    runtime: Runtime,
+    shared: Arc<Shared>,
+    tasks: Vec<JoinHandle<()>>,
+    standalone_started: bool,
+    closed: bool,
+}
+
+/// Yields messages delivered by an HTTP client transport's background tasks.
+pub struct HttpReceiver {
+    pub(crate) incoming: UnboundedReceiver<String>,
+}
+
+struct Shared {
+    http: reqwest::Client,
+    url: String,
+    session_id: Mutex<Option<String>>,
+    /// The protocol version the server answered `initialize` with.
+    protocol_version: Mutex<Option<String>>,
+    /// Taken on close, so the receiver sees end of stream once tasks stop.
+    incoming: Mutex<Option<UnboundedSender<String>>>,
+}
+
+impl StreamableHttpClientTransport {
+    /// Creates a transport for the MCP endpoint at `url`. No request is made
+    /// until the first message is sent.
+    pub fn new(url: &str) -> Result<Self, Box<dyn Error>> {
+        let runtime = tokio::runtime::Builder::new_multi_thread()
+            .worker_threads(1)
+            .enable_all()
+            .build()?;
+        let (incoming_sender, incoming) = unbounded_channel();
+        Ok(StreamableHttpClientTransport {
+            sender: StreamableHttpSender {
+                runtime,
+                shared: Arc::new(Shared {
+                    http: reqwest::Client::new(),
+                    url: url.to_string(),
+                    session_id: Mutex::new(None),
+                    protocol_version: Mutex::new(None),
+                    incoming: Mutex::new(Some(incoming_sender)),
+                }),
+                tasks: Vec::new(),
+                standalone_started: false,
+                closed: false,
+            },
+            receiver: HttpReceiver { incoming },
+        })
+    }
+
+    /// The session id assigned by the server, once initialized.
+    pub fn session_id(&self) -> Option<String> {
+        self.sender.session_id()
+    }
+}
+
+impl StreamableHttpSender {
+    /// The session id assigned by the server, once initialized.
+    pub fn session_id(&self) -> Option<String> {
+        self.shared.session_id.lock().unwrap().clone()
+    }
+}
+
+impl Shared {
+    fn request(&self, method: reqwest::Method) -> reqwest::RequestBuilder {
+        let mut request = self.http.request(method, &self.url);
+        if let Some(session_id) = self.session_id.lock().unwrap().as_deref() {
+            request = request.header(SESSION_ID_HEADER, session_id);
+        }
+        if let Some(version) = self.protocol_version.lock().unwrap().as_deref() {
+            request = request.header(PROTOCOL_VERSION_HEADER, version);
+        }
+        request
+    }
+
+    /// Opens a GET stream, resuming after `last_event_id` if given.
+    async fn get_stream(
+        &self,
+        last_event_id: Option<&str>,
+    ) -> Result<reqwest::Response, reqwest::Error> {
+        let mut request = self
+            .request(reqwest::Method::GET)
+            .header(ACCEPT, "text/event-stream");
+        if let Some(id) = last_event_id {
+            request = request.header(LAST_EVENT_ID_HEADER, id);
+        }
+        request.send().await
+    }
+
+    fn deliver(&self, message: String) {
+        // Later requests must name the version negotiated by `initialize`
+        let mut protocol_version = self.protocol_version.lock().unwrap();
+        if protocol_version.is_none() {
+            *protocol_version = serde_json::from_str::<serde_json::Value>(&message)
+                .ok()
+                .and_then(|value| value.pointer("/result/protocolVersion")?.as_str().map(String::from));
+        }
+        drop(protocol_version);
+        if let Some(incoming) = self.incoming.lock().unwrap().as_ref() {
+            let _ = incoming.send(message);
+        }
+    }
+
+    /// Reads an SSE stream to completion, delivering every message. When the
+    /// stream drops before `pending` responses have all arrived (or at all, for
+    /// the standalone stream), reconnects with the last seen event id. Responses
+    /// still missing when the stream is given up on are answered with an error.
+    async fn pump_stream(
+        self: Arc<Self>,
+        response: reqwest::Response,
+        mut pending: HashSet<String>,
+        standalone: bool,
+    ) {
+        let mut response = Some(response);
+        let mut last_event_id: Option<String> = None;
+        let mut retry = DEFAULT_RETRY;
+        let mut attempts = 0;
+
+        let reason = loop {
+            let mut current = match response.take() {
+                Some(response) => response,
+                None => {
+                    if attempts >= MAX_RESUME_ATTEMPTS {
+                        break format!("Gave up on SSE stream after {} attempts", attempts);
+                    }
+                    attempts += 1;
+                    tokio::time::sleep(retry).await;
+                    match self.get_stream(last_event_id.as_deref()).await {
+                        Ok(r) if r.status().is_success() => r,
+                        // The server has no GET stream, or the session is gone
+                        Ok(r) if r.status().is_client_error() => {
+                            break format!("SSE stream could not be resumed: {}", r.status());
+                        }
+                        Ok(_) | Err(_) => continue,
+                    }
+                }
+            };
+
+            let mut parser = SseParser::default();
+            while let Ok(Some(chunk)) = current.chunk().await {
+                for event in parser.push(&chunk) {
+                    if let Some(id) = event.id {
+                        last_event_id = Some(id);
+                    }
+                    if let Some(ms) = event.retry {
+                        retry = Duration::from_millis(ms);
+                    }
+                    if event.data.is_empty() {
+                        continue;
+                    }
+                    attempts = 0;
+                    for id in response_ids(&event.data) {
+                        pending.remove(&id);
+                    }
+                    self.deliver(event.data);
+                }
+                if !standalone && pending.is_empty() {
+                    return;
+                }
+            }
+
+            if !standalone && pending.is_empty() {
+                return;
+            }
+            if !standalone && last_event_id.is_none() {
+                break "SSE stream ended before all responses arrived and cannot be resumed"
+                    .to_string();
+            }
+        };
+
+        eprintln!("Client: {}", reason);
+        self.fail(pending, &reason);
+    }
+
+    /// POSTs a message and delivers the responses to the requests in `pending`,
+    /// whether they come back as a JSON body or an SSE stream.
+    async fn post(
+        self: Arc<Self>,
+        body: String,
+        pending: HashSet<String>,
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let response = self
+            .request(reqwest::Method::POST)
+            .header(ACCEPT, "application/json, text/event-stream")
+            .header(CONTENT_TYPE, "application/json")
+            .body(body)
+            .send()
+            .await?;
+
+        let status = response.status();
+        if status == StatusCode::NOT_FOUND && self.session_id.lock().unwrap().is_some() {
+            return Err("HTTP session expired or was terminated by the server".into());
+        }
+        if !status.is_success() {
+            let text = response.text().await.unwrap_or_default();
+            return Err(format!("HTTP request failed with status {}: {}", status, text).into());
+        }
+
+        if let Some(session_id) = response
+            .headers()
+            .get(SESSION_ID_HEADER)
+            .and_then(|v| v.to_str().ok())
+        {
+            *self.session_id.lock().unwrap() = Some(session_id.to_string());
+        }
+
+        if status == StatusCode::ACCEPTED || pending.is_empty() {
+            // Nothing to wait for
+        } else if is_event_stream(response.headers()) {
+            self.pump_stream(response, pending, false).await;
+        } else {
+            let text = response.text().await?;
+            match serde_json::from_str::<serde_json::Value>(&text)? {
+                serde_json::Value::Array(responses) => {
+                    for response in responses {
+                        self.deliver(response.to_string());
+                    }
+                }
+                _ => self.deliver(text),
+            }
+        }
+        Ok(())
+    }
+
+    /// Answers the requests in `pending` with an error, so their callers stop
+    /// waiting for responses that will never come.
+    fn fail(&self, pending: HashSet<String>, message: &str) {
+        for id in pending {
+            let response = serde_json::json!({
+                "jsonrpc": "2.0",
+                "id": serde_json::from_str::<serde_json::Value>(&id).unwrap_or_default(),
+                "error": { "code": -32603, "message": message },
+            });
+            self.deliver(response.to_string());
+        }
+    }
+}
+
+/// Returns the ids (as JSON text) of a JSON-RPC response or of each response in
+/// a batch; other messages have none.
+fn response_ids(message: &str) -> Vec<String> {
+    let Ok(value) = serde_json::from_str::<serde_json::Value>(message) else {
+        return Vec::new();
+    };
+    let responses = match &value {
+        serde_json::Value::Array(responses) => responses.iter().collect(),
+        response => vec![response],
+    };
+    responses
+        .into_iter()
+        .filter(|response| response.get("method").is_none())
+        .filter_map(|response| response.get("id").map(|id| id.to_string()))
+        .collect()
+}
+
+fn is_event_stream(headers: &HeaderMap) -> bool {
+    headers
+        .get(CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.starts_with("text/event-stream"))
+}
+
+impl TransportSender for StreamableHttpSender {
+    fn send(&mut self, message: &str) -> Result<(), Box<dyn Error>> {
+        if self.closed {
+            return Err("Transport is closed".into());
+        }
+
+        // Requests whose responses the reply must carry
+        let parsed: serde_json::Value = serde_json::from_str(message)?;
+        let messages = match &parsed {
+            serde_json::Value::Array(messages) => messages.iter().collect(),
+            message => vec![message],
+        };
+        let pending: HashSet<String> = messages
+            .iter()
+            .filter(|m| m.get("method").is_some())
+            .filter_map(|m| m.get("id").map(|id| id.to_string()))
+            .collect();
+
+        let shared = self.shared.clone();
+        let body = message.to_string();
+        self.tasks.retain(|task| !task.is_finished());
+        if pending.is_empty() || self.session_id().is_none() {
+            // The server accepts notifications and responses right away, and
+            // initialization must finish before anything else is sent, so these
+            // go out in order
+            self.runtime
+                .block_on(shared.post(body, pending))
+                .map_err(|e| e as Box<dyn Error>)?;
+        } else {
+            // Replies to requests may take as long as the request runs, so they
+            // are awaited on their own task, keeping later sends from waiting
+            let task = self.runtime.spawn(async move {
+                let ids = pending.clone();
+                if let Err(e) = shared.clone().post(body, pending).await {
+                    eprintln!("Client: {}", e);
+                    shared.fail(ids, &e.to_string());
+                }
+            });
+            self.tasks.push(task);
+        }
+
+        // Listen for server-initiated messages once the session exists
+        if !self.standalone_started && self.session_id().is_some() {
+            self.standalone_started = true;
+            let shared = self.shared.clone();
+            let task = self.runtime.spawn(async move {
+                match shared.get_stream(None).await {
+                    Ok(response) if response.status().is_success() => {
+                        shared.pump_stream(response, HashSet::new(), true).await;
+                    }
+                    _ => {} // Server does not offer a GET stream
+                }
+            });
+            self.tasks.push(task);
+        }
+
+        Ok(())
+    }
+
+    fn close(&mut self) -> Result<(), Box<dyn Error>> {
+        if self.closed {
+            return Ok(());
+        }
+        self.closed = true;
+        self.shared.incoming.lock().unwrap().take();
+        for task in self.tasks.drain(..) {
+            task.abort();
+        }
+        // Tell the server the session is over
+        if self.session_id().is_some() {
+            let shared = self.shared.clone();
+            self.runtime
+                .block_on(async move { shared.request(reqwest::Method::DELETE).send().await })?;
+        }
+        Ok(())
+    }
+}
+
+impl TransportReceiver for HttpReceiver {
+    fn receive(&mut self) -> Result<Option<String>, Box<dyn Error>> {
+        Ok(self.incoming.blocking_recv())
+    }
+}
+
+impl Transport for StreamableHttpClientTransport {
+    fn send(&mut self, message: &str) -> Result<(), Box<dyn Error>> {
+        self.sender.send(message)
+    }
+
+    fn receive(&mut self) -> Result<Option<String>, Box<dyn Error>> {
+        if self.sender.closed {
+            return Ok(None);
+        }
+        self.receiver.receive()
+    }
+
+    fn close(&mut self) -> Result<(), Box<dyn Error>> {
+        self.sender.close()
+    }
+
+    fn split(self: Box<Self>) -> (Box<dyn TransportSender>, Box<dyn TransportReceiver>) {
+        (Box::new(self.sender), Box::new(self.receiver))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::Router;
+    use axum::response::{IntoResponse, Response};
+    use axum::routing::post;
+    use serde_json::{Value, json};
+    use std::time::Instant;
+
+    /// Answers every request with a JSON body, holding back `slow` ones and
+    /// dropping the stream of `dropped` ones. Results
+    /// echo the protocol version header the request carried.
+    async fn answer(headers: axum::http::HeaderMap, body: String) -> Response {
+        let message: Value = serde_json::from_str(&body).unwrap();
+        let Some(id) = message.get("id") else {
+            return StatusCode::ACCEPTED.into_response();
+        };
+        if message["method"] == "slow" {
+            tokio::time::sleep(Duration::from_millis(500)).await;
+        }
+        if message["method"] == "dropped" {
+            // An SSE stream that ends without a response or an event id to resume from
+            let headers = [(SESSION_ID_HEADER, "test-session"), ("content-type", "text/event-stream")];
+            return (headers, ": keep-alive\n\n").into_response();
+        }
+        let result = match message["method"].as_str() {
+            Some("initialize") => json!({ "protocolVersion": "2025-06-18" }),
+            _ => {
+                let version = headers.get(PROTOCOL_VERSION_HEADER).map(|v| v.to_str().unwrap());
+                json!({ "protocolVersionHeader": version })
+            }
+        };
+        let response = json!({ "jsonrpc": "2.0", "id": id, "result": result });
+        ([(SESSION_ID_HEADER, "test-session")], axum::Json(response)).into_response()
+    }
+
+    /// Serves `answer` on an ephemeral port from its own thread, since the
+    /// transport blocks, and returns the endpoint URL.
+    fn start_json_server() -> String {
+        let (url_sender, url) = std::sync::mpsc::channel();
+        std::thread::spawn(move || {
+            let runtime = tokio::runtime::Runtime::new().unwrap();
+            runtime.block_on(async move {
+                let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+                let url = format!("http://{}/mcp", listener.local_addr().unwrap());
+                url_sender.send(url).unwrap();
+                let app = Router::new().route("/mcp", post(answer));
+                axum::serve(listener, app).await.unwrap();
+            });
+        });
+        url.recv().unwrap()
+    }
+
+    fn request(id: i64, method: &str) -> String {
+        json!({ "jsonrpc": "2.0", "id": id, "method": method }).to_string()
+    }
+
+    fn receive_id(transport: &mut StreamableHttpClientTransport) -> Value {
+        let message = transport.receive().unwrap().unwrap();
+        serde_json::from_str::<Value>(&message).unwrap()["id"].clone()
+    }
+
+    #[test]
+    fn slow_json_responses_do_not_hold_up_later_sends() {
+        let mut transport = StreamableHttpClientTransport::new(&start_json_server()).unwrap();
+        transport.send(&request(1, "initialize")).unwrap();
+        assert_eq!(receive_id(&mut transport), 1);
+        assert_eq!(transport.session_id().as_deref(), Some("test-session"));
+
+        let started = Instant::now();
+        transport.send(&request(2, "slow")).unwrap();
+        transport.send(&request(3, "fast")).unwrap();
+        assert!(started.elapsed() < Duration::from_millis(400));
+
+        assert_eq!(receive_id(&mut transport), 3);
+        assert_eq!(receive_id(&mut transport), 2);
+    }
+
+    #[test]
+    fn sends_the_negotiated_protocol_version() {
+        let mut transport = StreamableHttpClientTransport::new(&start_json_server()).unwrap();
+        transport.send(&request(1, "initialize")).unwrap();
+        assert_eq!(receive_id(&mut transport), 1);
+
+        transport.send(&request(2, "ping")).unwrap();
+        let message = transport.receive().unwrap().unwrap();
+        let response: Value = serde_json::from_str(&message).unwrap();
+        assert_eq!(response["result"]["protocolVersionHeader"], "2025-06-18");
+    }
+
+    #[test]
+    fn streams_dropped_without_an_event_id_fail_their_requests() {
+        let mut transport = StreamableHttpClientTransport::new(&start_json_server()).unwrap();
+        transport.send(&request(1, "initialize")).unwrap();
+        assert_eq!(receive_id(&mut transport), 1);
+
+        transport.send(&request(2, "dropped")).unwrap();
+        let message = transport.receive().unwrap().unwrap();
+        let response: Value = serde_json::from_str(&message).unwrap();
+        assert_eq!(response["id"], 2);
+        assert_eq!(response["error"]["code"], -32603);
+    }
+}
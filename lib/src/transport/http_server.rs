@@ -0,0 +1,1189 @@
+use axum::Router;
+use axum::body::Body;
+use axum::extract::State;
+use axum::http::{HeaderMap, HeaderValue, StatusCode, header};
+use axum::response::sse::{Event, KeepAlive, Sse};
+use axum::response::{IntoResponse, Response};
+use axum::routing::post;
+use std::collections::{HashMap, VecDeque};
+use std::convert::Infallible;
+use std::error::Error;
+use std::sync::{Arc, Mutex};
+use tokio::sync::mpsc::{UnboundedReceiver, UnboundedSender, unbounded_channel};
+use tokio_stream::StreamExt;
+use tokio_stream::wrappers::UnboundedReceiverStream;
+
+use super::{Transport, TransportReceiver, TransportSender};
+use crate::protocol::{self, SUPPORTED_PROTOCOL_VERSIONS};
+
+/// Header carrying the session id assigned at initialization.
+pub const SESSION_ID_HEADER: &str = "mcp-session-id";
+
+/// Header carrying the protocol version negotiated at initialization, sent by
+/// the client on every later request.
+pub const PROTOCOL_VERSION_HEADER: &str = "mcp-protocol-version";
+
+/// Header a client sends to resume an SSE stream after the given event.
+pub const LAST_EVENT_ID_HEADER: &str = "last-event-id";
+
+/// Number of sent events kept per session for replay on resumption.
+const EVENT_HISTORY: usize = 1024;
+
+type RunSession = dyn Fn(Box<dyn Transport>) -> Result<(), Box<dyn Error>> + Send + Sync;
+
+/// An HTTP error status with a short plain-text explanation.
+pub(crate) type Rejection = (StatusCode, &'static str);
+
+/// Serves MCP over the Streamable HTTP transport on a single endpoint `path`.
+///
+/// Every session (started by a POSTed `initialize` request) gets its own
+/// transport, handed to `run_session` on a dedicated thread; typically it
+/// builds a Server over the transport and runs it. The session ends when
+/// `run_session` returns. Blocks until the listener fails.
+pub fn serve<F, E>(addr: &str, path: &str, run_session: F) -> Result<(), Box<dyn Error>>
+where
+    F: Fn(Box<dyn Transport>) -> Result<(), E> + Send + Sync + 'static,
+    E: Into<Box<dyn Error>>,
+{
+    let app = router(path, run_session);
+    let runtime = tokio::runtime::Runtime::new()?;
+    runtime.block_on(async {
+        let listener = tokio::net::TcpListener::bind(addr).await?;
+        eprintln!(
+            "Server: Listening for Streamable HTTP on http://{}{}",
+            listener.local_addr()?,
+            path
+        );
+        axum::serve(listener, app).await
+    })?;
+    Ok(())
+}
+
+/// Routes the MCP endpoint at `path` to sessions run by `run_session`.
+fn router<F, E>(path: &str, run_session: F) -> Router
+where
+    F: Fn(Box<dyn Transport>) -> Result<(), E> + Send + Sync + 'static,
+    E: Into<Box<dyn Error>>,
+{
+    let state = Arc::new(HttpServerState {
+        run_session: Arc::new(move |transport| run_session(transport).map_err(Into::into)),
+        sessions: Mutex::new(HashMap::new()),
+    });
+    Router::new()
+        .route(path, post(handle_post).get(handle_get).delete(handle_delete))
+        .with_state(state)
+}
+
+struct HttpServerState {
+    run_session: Arc<RunSession>,
+    sessions: Mutex<HashMap<String, Arc<Session>>>,
+}
+
+impl HttpServerState {
+    /// Creates a session and starts running it on a new thread.
+    fn start_session(self: &Arc<Self>) -> Arc<Session> {
+        let (incoming_sender, incoming) = unbounded_channel();
+        let session = Arc::new(Session {
+            id: uuid::Uuid::new_v4().to_string(),
+            incoming: Mutex::new(Some(incoming_sender)),
+            outgoing: Mutex::new(Outgoing::default()),
+            protocol_version: Mutex::new(None),
+        });
+        self.sessions
+            .lock()
+            .unwrap()
+            .insert(session.id.clone(), session.clone());
+
+        let transport = SessionTransport {
+            sender: SessionSender {
+                session: session.clone(),
+            },
+            receiver: SessionReceiver { incoming },
+        };
+        let state = self.clone();
+        let session_id = session.id.clone();
+        std::thread::spawn(move || {
+            eprintln!("Server: Session {} started", session_id);
+            if let Err(e) = (state.run_session)(Box::new(transport)) {
+                eprintln!("Server: Session {} ended with error: {}", session_id, e);
+            }
+            state.end_session(&session_id);
+        });
+
+        session
+    }
+
+    fn end_session(&self, id: &str) {
+        if let Some(session) = self.sessions.lock().unwrap().remove(id) {
+            session.shutdown();
+            eprintln!("Server: Session {} closed", id);
+        }
+    }
+
+    /// Resolves the session named by the request headers.
+    fn session(&self, headers: &HeaderMap) -> Result<Arc<Session>, Rejection> {
+        let id = headers
+            .get(SESSION_ID_HEADER)
+            .and_then(|v| v.to_str().ok())
+            .ok_or((StatusCode::BAD_REQUEST, "Missing Mcp-Session-Id header"))?;
+        self.sessions
+            .lock()
+            .unwrap()
+            .get(id)
+            .cloned()
+            .ok_or((StatusCode::NOT_FOUND, "Unknown session"))
+    }
+}
+
+/// Server-side state of one MCP session.
+struct Session {
+    id: String,
+    /// Messages from the client to the session. None once closed.
+    incoming: Mutex<Option<UnboundedSender<String>>>,
+    outgoing: Mutex<Outgoing>,
+    /// The protocol version agreed on in `initialize`, once answered.
+    protocol_version: Mutex<Option<String>>,
+}
+
+/// Routing of messages from the session back to open HTTP streams.
+#[derive(Default)]
+struct Outgoing {
+    next_event_id: u64,
+    next_stream_id: u64,
+    /// Recently sent events, kept so dropped streams can be resumed.
+    history: VecDeque<StoredEvent>,
+    streams: HashMap<u64, OpenStream>,
+    /// Maps a pending request id (as JSON text) to the stream owed its response.
+    /// All requests of a batch map to the stream owed the batch's response.
+    requests: HashMap<String, u64>,
+    /// Streams owed a reply that carries no id, such as the error for a
+    /// malformed message, oldest first.
+    anonymous: VecDeque<u64>,
+    /// The stream opened by GET for server-initiated messages.
+    standalone: Option<u64>,
+}
+
+struct OpenStream {
+    /// Live connection, if the client is currently attached.
+    sender: Option<UnboundedSender<StoredEvent>>,
+    /// Replies still owed on this stream, a batch's array counting as one; it
+    /// closes when this reaches zero. Always zero for the standalone stream,
+    /// which stays open.
+    pending: usize,
+}
+
+#[derive(Clone)]
+struct StoredEvent {
+    id: u64,
+    stream: u64,
+    data: String,
+}
+
+impl Outgoing {
+    fn open_stream(&mut self, pending: usize) -> (u64, UnboundedReceiver<StoredEvent>) {
+        let (sender, receiver) = unbounded_channel();
+        let id = self.next_stream_id;
+        self.next_stream_id += 1;
+        self.streams.insert(
+            id,
+            OpenStream {
+                sender: Some(sender),
+                pending,
+            },
+        );
+        (id, receiver)
+    }
+
+    /// Finds the stream owed a reply and stops waiting for it there. A reply
+    /// goes where any of the ids it carries is pending; one without ids goes to
+    /// the oldest stream owed such a reply.
+    fn reply_stream(&mut self, reply: &serde_json::Value) -> Option<u64> {
+        let replies = match reply {
+            serde_json::Value::Array(replies) => replies.iter().collect(),
+            reply => vec![reply],
+        };
+        let ids: Vec<String> = replies
+            .iter()
+            .filter_map(|reply| reply.get("id"))
+            .filter(|id| !id.is_null())
+            .map(|id| id.to_string())
+            .collect();
+        let stream = if ids.is_empty() {
+            loop {
+                let stream = self.anonymous.pop_front()?;
+                if self.streams.contains_key(&stream) {
+                    break stream;
+                }
+            }
+        } else {
+            ids.iter().find_map(|id| self.requests.get(id).copied())?
+        };
+        self.requests.retain(|_, owed| *owed != stream);
+        self.anonymous.retain(|owed| *owed != stream);
+        Some(stream)
+    }
+
+    /// Stops waiting for the reply to a cancelled request, since the Server
+    /// won't send one. A stream owed nothing else is closed.
+    fn cancel(&mut self, id: &str) {
+        let Some(stream) = self.requests.remove(id) else {
+            return; // Already answered
+        };
+        let owed = self.requests.values().any(|owed| *owed == stream)
+            || self.anonymous.contains(&stream);
+        if !owed {
+            self.streams.remove(&stream);
+        }
+    }
+
+    fn record(&mut self, stream: u64, data: String) -> StoredEvent {
+        self.next_event_id += 1;
+        let event = StoredEvent {
+            id: self.next_event_id,
+            stream,
+            data,
+        };
+        self.history.push_back(event.clone());
+        if self.history.len() > EVENT_HISTORY {
+            self.history.pop_front();
+        }
+        event
+    }
+}
+
+impl Session {
+    fn forward(&self, message: String) -> bool {
+        match self.incoming.lock().unwrap().as_ref() {
+            Some(sender) => sender.send(message).is_ok(),
+            None => false,
+        }
+    }
+
+    /// Delivers a message to the client: responses, and arrays of them, go to
+    /// the stream of the request they answer, everything else to the standalone
+    /// GET stream.
+    fn route(&self, message: &str) -> Result<(), Box<dyn Error>> {
+        let value: serde_json::Value = serde_json::from_str(message)?;
+        let is_reply = value.is_array() || value.get("method").is_none();
+        if let Some(version) = value
+            .pointer("/result/protocolVersion")
+            .and_then(|v| v.as_str())
+        {
+            self.protocol_version
+                .lock()
+                .unwrap()
+                .get_or_insert_with(|| version.to_string());
+        }
+
+        let mut outgoing = self.outgoing.lock().unwrap();
+        let stream = if is_reply {
+            outgoing.reply_stream(&value)
+        } else {
+            outgoing.standalone
+        };
+        let Some(stream) = stream else {
+            eprintln!(
+                "Server: No open stream for outgoing message in session {}. Dropping.",
+                self.id
+            );
+            return Ok(());
+        };
+
+        let event = outgoing.record(stream, message.to_string());
+        if let Some(open) = outgoing.streams.get_mut(&stream) {
+            if let Some(sender) = &open.sender
+                && sender.send(event).is_err()
+            {
+                // Client went away; the event stays in history for resumption
+                open.sender = None;
+            }
+            if is_reply {
+                open.pending = open.pending.saturating_sub(1);
+                if open.pending == 0 {
+                    outgoing.streams.remove(&stream);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn shutdown(&self) {
+        self.incoming.lock().unwrap().take();
+        let mut outgoing = self.outgoing.lock().unwrap();
+        outgoing.streams.clear();
+        outgoing.requests.clear();
+        outgoing.anonymous.clear();
+        outgoing.standalone = None;
+    }
+}
+
+/// The Transport handed to a session's Server.
+struct SessionTransport {
+    sender: SessionSender,
+    receiver: SessionReceiver,
+}
+
+struct SessionSender {
+    session: Arc<Session>,
+}
+
+struct SessionReceiver {
+    incoming: UnboundedReceiver<String>,
+}
+
+impl TransportSender for SessionSender {
+    fn send(&mut self, message: &str) -> Result<(), Box<dyn Error>> {
+        self.session.route(message)
+    }
+
+    fn close(&mut self) -> Result<(), Box<dyn Error>> {
+        self.session.incoming.lock().unwrap().take();
+        Ok(())
+    }
+}
+
+impl TransportReceiver for SessionReceiver {
+    fn receive(&mut self) -> Result<Option<String>, Box<dyn Error>> {
+        Ok(self.incoming.blocking_recv())
+    }
+}
+
+impl Transport for SessionTransport {
+    fn send(&mut self, message: &str) -> Result<(), Box<dyn Error>> {
+        self.sender.send(message)
+    }
+
+    fn receive(&mut self) -> Result<Option<String>, Box<dyn Error>> {
+        self.receiver.receive()
+    }
+
+    fn close(&mut self) -> Result<(), Box<dyn Error>> {
+        self.sender.close()
+    }
+
+    fn split(self: Box<Self>) -> (Box<dyn TransportSender>, Box<dyn TransportReceiver>) {
+        (Box::new(self.sender), Box::new(self.receiver))
+    }
+}
+
+fn accepts_event_stream(headers: &HeaderMap) -> bool {
+    headers
+        .get(header::ACCEPT)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.contains("text/event-stream"))
+}
+
+/// Rejects browser requests from non-local origins to guard against DNS rebinding.
+pub(crate) fn check_origin(headers: &HeaderMap) -> Result<(), Rejection> {
+    let Some(origin) = headers.get(header::ORIGIN).and_then(|v| v.to_str().ok()) else {
+        return Ok(());
+    };
+    let authority = origin.split_once("://").map_or(origin, |(_, rest)| rest);
+    let host = match authority.rsplit_once(':') {
+        Some((host, port)) if port.chars().all(|c| c.is_ascii_digit()) => host,
+        _ => authority,
+    };
+    if matches!(host, "localhost" | "127.0.0.1" | "[::1]") {
+        Ok(())
+    } else {
+        Err((StatusCode::FORBIDDEN, "Origin not allowed"))
+    }
+}
+
+/// Rejects requests naming a protocol version other than the one the session
+/// agreed on in `initialize`, or, before that, one this server doesn't speak.
+/// Without the header, clients are assumed to speak 2025-03-26 as the spec says.
+fn check_protocol_version(headers: &HeaderMap, session: Option<&Session>) -> Result<(), Rejection> {
+    let Some(version) = headers.get(PROTOCOL_VERSION_HEADER) else {
+        return Ok(());
+    };
+    let version = version.to_str().unwrap_or_default();
+    let negotiated = session.and_then(|session| session.protocol_version.lock().unwrap().clone());
+    let accepted = match negotiated {
+        Some(negotiated) => version == negotiated,
+        None => SUPPORTED_PROTOCOL_VERSIONS.contains(&version),
+    };
+    if accepted {
+        Ok(())
+    } else {
+        Err((StatusCode::BAD_REQUEST, "Unsupported MCP-Protocol-Version"))
+    }
+}
+
+fn json_rpc_error_response(status: StatusCode, code: i64, message: &str) -> Response {
+    let body = serde_json::json!({
+        "jsonrpc": "2.0",
+        "id": null,
+        "error": { "code": code, "message": message },
+    });
+    (status, axum::Json(body)).into_response()
+}
+
+fn with_session_header(mut response: Response, session: &Session) -> Response {
+    if let Ok(value) = HeaderValue::from_str(&session.id) {
+        response.headers_mut().insert(SESSION_ID_HEADER, value);
+    }
+    response
+}
+
+fn sse_response(receiver: UnboundedReceiver<StoredEvent>) -> Response {
+    let stream = UnboundedReceiverStream::new(receiver).map(|event| {
+        Ok::<_, Infallible>(Event::default().id(event.id.to_string()).data(event.data))
+    });
+    Sse::new(stream)
+        .keep_alive(KeepAlive::default())
+        .into_response()
+}
+
+async fn handle_post(
+    State(state): State<Arc<HttpServerState>>,
+    headers: HeaderMap,
+    body: String,
+) -> Response {
+    if let Err(rejection) = check_origin(&headers) {
+        return rejection.into_response();
+    }
+
+    let parsed: serde_json::Value = match serde_json::from_str(&body) {
+        Ok(value) => value,
+        Err(_) => return json_rpc_error_response(StatusCode::BAD_REQUEST, -32700, "Parse error"),
+    };
+
+    // Only an initialize request may come without a session; it starts one
+    let existing = if headers.contains_key(SESSION_ID_HEADER) {
+        match state.session(&headers) {
+            Ok(session) => Some(session),
+            Err(rejection) => return rejection.into_response(),
+        }
+    } else if parsed["method"] == "initialize" {
+        None
+    } else {
+        return (StatusCode::BAD_REQUEST, "Missing Mcp-Session-Id header").into_response();
+    };
+    if let Err(rejection) = check_protocol_version(&headers, existing.as_deref()) {
+        return rejection.into_response();
+    }
+    let session = existing.unwrap_or_else(|| state.start_session());
+
+    // Batches go to the Server whole, so it applies the same rules as on stdio
+    let batching = session
+        .protocol_version
+        .lock()
+        .unwrap()
+        .as_deref()
+        .is_some_and(protocol::supports_batching);
+    let reply = expected_reply(&parsed, batching);
+
+    // Register the stream before forwarding so no reply can race past it
+    for id in cancelled_requests(&parsed) {
+        session.outgoing.lock().unwrap().cancel(&id);
+    }
+    let receiver = match reply {
+        Reply::None => None,
+        Reply::Ids(ids) => {
+            let mut outgoing = session.outgoing.lock().unwrap();
+            let (stream, receiver) = outgoing.open_stream(1);
+            for id in ids {
+                outgoing.requests.insert(id, stream);
+            }
+            Some(receiver)
+        }
+        Reply::Anonymous => {
+            let mut outgoing = session.outgoing.lock().unwrap();
+            let (stream, receiver) = outgoing.open_stream(1);
+            outgoing.anonymous.push_back(stream);
+            Some(receiver)
+        }
+    };
+
+    if !session.forward(body) {
+        return (StatusCode::NOT_FOUND, "Session has ended").into_response();
+    }
+
+    let Some(mut receiver) = receiver else {
+        return with_session_header(StatusCode::ACCEPTED.into_response(), &session);
+    };
+
+    if accepts_event_stream(&headers) {
+        return with_session_header(sse_response(receiver), &session);
+    }
+
+    // Plain JSON: wait for the reply owed to this POST
+    let response = match receiver.recv().await {
+        Some(event) => ([(header::CONTENT_TYPE, "application/json")], event.data).into_response(),
+        None => StatusCode::ACCEPTED.into_response(),
+    };
+    with_session_header(response, &session)
+}
+
+/// Ids (as JSON text) of the requests a message cancels with
+/// `notifications/cancelled`, itself or as a batch member.
+fn cancelled_requests(message: &serde_json::Value) -> Vec<String> {
+    let messages = match message {
+        serde_json::Value::Array(messages) => messages.iter().collect(),
+        message => vec![message],
+    };
+    messages
+        .into_iter()
+        .filter(|message| message["method"] == "notifications/cancelled")
+        .filter_map(|message| message.pointer("/params/requestId"))
+        .map(|id| id.to_string())
+        .collect()
+}
+
+/// What the Server answers a POSTed message with.
+enum Reply {
+    /// Nothing, as for notifications and responses from the client.
+    None,
+    /// A response, or a batch's array of them, carrying at least one of these
+    /// ids (as JSON text).
+    Ids(Vec<String>),
+    /// An error without an id, as for a message too malformed to have one or
+    /// a batch that is rejected as a whole.
+    Anonymous,
+}
+
+fn expected_reply(message: &serde_json::Value, batching: bool) -> Reply {
+    let serde_json::Value::Array(batch) = message else {
+        return expected_member_reply(message);
+    };
+    if batch.is_empty() || !batching {
+        return Reply::Anonymous;
+    }
+    let mut ids = Vec::new();
+    let mut anonymous = false;
+    for member in batch {
+        match expected_member_reply(member) {
+            Reply::None => {}
+            Reply::Ids(member_ids) => ids.extend(member_ids),
+            Reply::Anonymous => anonymous = true,
+        }
+    }
+    match (ids.is_empty(), anonymous) {
+        (false, _) => Reply::Ids(ids),
+        (true, true) => Reply::Anonymous,
+        (true, false) => Reply::None,
+    }
+}
+
+/// Classifies a single message the way the Server does: invalid messages are
+/// answered with an error carrying their id, if they have a usable one.
+fn expected_member_reply(message: &serde_json::Value) -> Reply {
+    let valid = message["jsonrpc"] == "2.0";
+    let id = match message.get("id") {
+        Some(id @ (serde_json::Value::Number(_) | serde_json::Value::String(_))) => {
+            Some(id.to_string())
+        }
+        _ => None,
+    };
+    let is_notification = message["method"].is_string() && message.get("id").is_none();
+    let is_response = message.get("method").is_none()
+        && (message.get("result").is_some() || message.get("error").is_some());
+    match id {
+        _ if valid && (is_notification || is_response) => Reply::None,
+        Some(id) => Reply::Ids(vec![id]),
+        None => Reply::Anonymous,
+    }
+}
+
+async fn handle_get(State(state): State<Arc<HttpServerState>>, headers: HeaderMap) -> Response {
+    if let Err(rejection) = check_origin(&headers) {
+        return rejection.into_response();
+    }
+    if !accepts_event_stream(&headers) {
+        return (StatusCode::NOT_ACCEPTABLE, "Client must accept text/event-stream").into_response();
+    }
+    let session = match state.session(&headers) {
+        Ok(session) => session,
+        Err(rejection) => return rejection.into_response(),
+    };
+    if let Err(rejection) = check_protocol_version(&headers, Some(&session)) {
+        return rejection.into_response();
+    }
+
+    let last_event_id = headers
+        .get(LAST_EVENT_ID_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse::<u64>().ok());
+
+    let mut outgoing = session.outgoing.lock().unwrap();
+    let resumed = last_event_id.and_then(|last| {
+        outgoing
+            .history
+            .iter()
+            .find(|event| event.id == last)
+            .map(|event| (last, event.stream))
+    });
+
+    let receiver = match resumed {
+        // Replay what was missed on the dropped stream, then reattach to it
+        Some((last, stream)) => {
+            let (sender, receiver) = unbounded_channel();
+            for event in outgoing
+                .history
+                .iter()
+                .filter(|e| e.stream == stream && e.id > last)
+            {
+                let _ = sender.send(event.clone());
+            }
+            if let Some(open) = outgoing.streams.get_mut(&stream) {
+                open.sender = Some(sender);
+            }
+            receiver
+        }
+        None => {
+            if let Some(previous) = outgoing.standalone.take() {
+                outgoing.streams.remove(&previous);
+            }
+            let (stream, receiver) = outgoing.open_stream(0);
+            outgoing.standalone = Some(stream);
+            receiver
+        }
+    };
+    drop(outgoing);
+
+    with_session_header(sse_response(receiver), &session)
+}
+
+async fn handle_delete(State(state): State<Arc<HttpServerState>>, headers: HeaderMap) -> Response {
+    if let Err(rejection) = check_origin(&headers) {
+        return rejection.into_response();
+    }
+    match state.session(&headers) {
+        Ok(session) => {
+            if let Err(rejection) = check_protocol_version(&headers, Some(&session)) {
+                return rejection.into_response();
+            }
+            state.end_session(&session.id);
+            Response::builder()
+                .status(StatusCode::OK)
+                .body(Body::empty())
+                .unwrap()
+        }
+        Err(rejection) => rejection.into_response(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::Server;
+    use crate::transport::sse::{SseEvent, SseParser};
+    use crate::types::{
+        AsyncToolImplementation, Tool, ToolContext, ToolsCallResult, success_content, text_content,
+    };
+    use async_trait::async_trait;
+    use serde_json::{Value, json};
+    use std::time::Duration;
+
+    const JSON: &str = "application/json";
+    const JSON_OR_SSE: &str = "application/json, text/event-stream";
+
+    /// Reports progress three times, a little apart, then answers.
+    struct Count;
+
+    #[async_trait]
+    impl AsyncToolImplementation for Count {
+        fn get_tool(&self) -> Tool {
+            Tool {
+                name: "count".to_string(),
+                description: "Counts to three".to_string(),
+                input_schema: json!({ "type": "object" }),
+            }
+        }
+
+        async fn call(&self, _arguments: Value, context: ToolContext) -> ToolsCallResult {
+            for step in 0..3 {
+                context.report_progress(step as f64, Some(3.0), None);
+                tokio::time::sleep(Duration::from_millis(50)).await;
+            }
+            success_content(vec![text_content("three".to_string())])
+        }
+    }
+
+    /// Takes far longer than any test waits.
+    struct Slow;
+
+    #[async_trait]
+    impl AsyncToolImplementation for Slow {
+        fn get_tool(&self) -> Tool {
+            Tool {
+                name: "slow".to_string(),
+                description: "Never finishes in time".to_string(),
+                input_schema: json!({ "type": "object" }),
+            }
+        }
+
+        async fn call(&self, _arguments: Value, _context: ToolContext) -> ToolsCallResult {
+            tokio::time::sleep(Duration::from_secs(60)).await;
+            success_content(Vec::new())
+        }
+    }
+
+    /// Logs its `text` argument to the calling client.
+    struct Log;
+
+    #[async_trait]
+    impl AsyncToolImplementation for Log {
+        fn get_tool(&self) -> Tool {
+            Tool {
+                name: "log".to_string(),
+                description: "Logs the given text".to_string(),
+                input_schema: json!({ "type": "object" }),
+            }
+        }
+
+        async fn call(&self, arguments: Value, context: ToolContext) -> ToolsCallResult {
+            context.logger().info(arguments["text"].as_str().unwrap_or_default());
+            success_content(Vec::new())
+        }
+    }
+
+    /// Serves a test server on an ephemeral port and returns its endpoint URL.
+    async fn start() -> String {
+        let app = router("/mcp", |transport| {
+            Server::builder("test", "1.0")
+                .transport(transport)
+                .async_tool(Arc::new(Count))
+                .async_tool(Arc::new(Slow))
+                .async_tool(Arc::new(Log))
+                .build()
+                .run()
+        });
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/mcp", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        url
+    }
+
+    struct TestClient {
+        http: reqwest::Client,
+        url: String,
+        session_id: Option<String>,
+    }
+
+    impl TestClient {
+        /// Starts a session on the server at `url`, offering `version`.
+        async fn initialize(url: &str, version: &str) -> TestClient {
+            let mut client = TestClient {
+                http: reqwest::Client::new(),
+                url: url.to_string(),
+                session_id: None,
+            };
+            let response = client
+                .post(
+                    json!({
+                        "jsonrpc": "2.0",
+                        "id": 0,
+                        "method": "initialize",
+                        "params": {
+                            "protocolVersion": version,
+                            "capabilities": {},
+                            "clientInfo": { "name": "test", "version": "1.0" },
+                        },
+                    }),
+                    JSON,
+                )
+                .await;
+            assert_eq!(response.status(), StatusCode::OK);
+            let session_id = response.headers().get(SESSION_ID_HEADER).unwrap();
+            client.session_id = Some(session_id.to_str().unwrap().to_string());
+
+            let initialized = json!({ "jsonrpc": "2.0", "method": "notifications/initialized" });
+            let response = client.post(initialized, JSON).await;
+            assert_eq!(response.status(), StatusCode::ACCEPTED);
+            client
+        }
+
+        fn request(&self, method: reqwest::Method) -> reqwest::RequestBuilder {
+            let mut request = self.http.request(method, &self.url);
+            if let Some(session_id) = &self.session_id {
+                request = request.header(SESSION_ID_HEADER, session_id);
+            }
+            request
+        }
+
+        async fn post(&self, message: Value, accept: &str) -> reqwest::Response {
+            self.request(reqwest::Method::POST)
+                .header(header::ACCEPT, accept)
+                .header(header::CONTENT_TYPE, JSON)
+                .body(message.to_string())
+                .send()
+                .await
+                .unwrap()
+        }
+
+        async fn get(&self, last_event_id: Option<&str>) -> reqwest::Response {
+            let mut request = self
+                .request(reqwest::Method::GET)
+                .header(header::ACCEPT, "text/event-stream");
+            if let Some(id) = last_event_id {
+                request = request.header(LAST_EVENT_ID_HEADER, id);
+            }
+            request.send().await.unwrap()
+        }
+    }
+
+    fn ping(id: i64) -> Value {
+        json!({ "jsonrpc": "2.0", "id": id, "method": "ping" })
+    }
+
+    fn content_type(response: &reqwest::Response) -> &str {
+        response.headers()[header::CONTENT_TYPE].to_str().unwrap()
+    }
+
+    /// Reads SSE events carrying data until `count` have arrived.
+    async fn read_events(response: &mut reqwest::Response, count: usize) -> Vec<SseEvent> {
+        let mut parser = SseParser::default();
+        let mut events = Vec::new();
+        while events.len() < count {
+            let chunk = tokio::time::timeout(Duration::from_secs(5), response.chunk())
+                .await
+                .expect("timed out waiting for an event")
+                .unwrap()
+                .expect("stream ended early");
+            events.extend(parser.push(&chunk).into_iter().filter(|e| !e.data.is_empty()));
+        }
+        events
+    }
+
+    async fn json_body(response: reqwest::Response) -> Value {
+        serde_json::from_str(&response.text().await.unwrap()).unwrap()
+    }
+
+    fn data(event: &SseEvent) -> Value {
+        serde_json::from_str(&event.data).unwrap()
+    }
+
+    #[tokio::test]
+    async fn session_id_is_issued_then_required() {
+        let url = start().await;
+        let client = TestClient::initialize(&url, "2025-06-18").await;
+
+        let mut anonymous = TestClient {
+            http: reqwest::Client::new(),
+            url: url.clone(),
+            session_id: None,
+        };
+        let response = anonymous.post(ping(1), JSON).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        anonymous.session_id = Some("no-such-session".to_string());
+        let response = anonymous.post(ping(1), JSON).await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        let response = client.post(ping(1), JSON).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            response.headers()[SESSION_ID_HEADER].to_str().unwrap(),
+            client.session_id.as_deref().unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn answers_with_json_or_sse_as_accepted() {
+        let url = start().await;
+        let client = TestClient::initialize(&url, "2025-06-18").await;
+
+        let response = client.post(ping(1), JSON).await;
+        assert!(content_type(&response).starts_with(JSON));
+        let body = json_body(response).await;
+        assert_eq!(body["id"], 1);
+        assert!(body.get("result").is_some());
+
+        let mut response = client.post(ping(2), JSON_OR_SSE).await;
+        assert!(content_type(&response).starts_with("text/event-stream"));
+        let events = read_events(&mut response, 1).await;
+        assert_eq!(data(&events[0])["id"], 2);
+        // The stream closes once its only response is sent
+        assert_eq!(response.chunk().await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn get_stream_resumes_after_last_event_id() {
+        let url = start().await;
+        let client = Arc::new(TestClient::initialize(&url, "2025-06-18").await);
+
+        // Progress goes to the GET stream; drop it after the first report
+        let mut stream = client.get(None).await;
+        assert_eq!(stream.status(), StatusCode::OK);
+        let call = json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "tools/call",
+            "params": { "name": "count", "arguments": {}, "_meta": { "progressToken": "p" } },
+        });
+        let caller = client.clone();
+        let call = tokio::spawn(async move { json_body(caller.post(call, JSON).await).await });
+        let first = read_events(&mut stream, 1).await.remove(0);
+        assert_eq!(data(&first)["params"]["progress"], 0.0);
+        drop(stream);
+
+        let result = call.await.unwrap();
+        assert_eq!(result["id"], 1);
+
+        // The reports sent while disconnected are replayed, in order
+        let mut resumed = client.get(first.id.as_deref()).await;
+        assert_eq!(resumed.status(), StatusCode::OK);
+        let missed: Vec<Value> = read_events(&mut resumed, 2).await.iter().map(data).collect();
+        assert_eq!(missed[0]["params"]["progress"], 1.0);
+        assert_eq!(missed[1]["params"]["progress"], 2.0);
+    }
+
+    #[tokio::test]
+    async fn delete_ends_the_session() {
+        let url = start().await;
+        let client = TestClient::initialize(&url, "2025-06-18").await;
+
+        let response = client.request(reqwest::Method::DELETE).send().await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let response = client.post(ping(1), JSON).await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        let response = client.request(reqwest::Method::DELETE).send().await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    fn error_code(response: &Value) -> i64 {
+        response["error"]["code"].as_i64().unwrap()
+    }
+
+    #[tokio::test]
+    async fn batches_are_rejected_outside_2025_03_26() {
+        let url = start().await;
+        let client = TestClient::initialize(&url, "2025-06-18").await;
+
+        let response = client.post(json!([ping(1), ping(2)]), JSON).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = json_body(response).await;
+        assert_eq!(error_code(&body), -32600);
+        assert_eq!(body["id"], Value::Null);
+    }
+
+    #[tokio::test]
+    async fn empty_batches_are_rejected() {
+        let url = start().await;
+        let client = TestClient::initialize(&url, "2025-03-26").await;
+
+        let body = json_body(client.post(json!([]), JSON).await).await;
+        assert_eq!(error_code(&body), -32600);
+        assert_eq!(body["id"], Value::Null);
+    }
+
+    #[tokio::test]
+    async fn batch_members_are_answered_together() {
+        let url = start().await;
+        let client = TestClient::initialize(&url, "2025-03-26").await;
+
+        let batch = json!([
+            ping(1),
+            { "jsonrpc": "2.0", "id": 2 },
+            5,
+            { "jsonrpc": "2.0", "method": "notifications/roots/list_changed" },
+            ping(3),
+        ]);
+        let body = json_body(client.post(batch, JSON).await).await;
+        let mut responses = body.as_array().unwrap().clone();
+        responses.sort_by_key(|response| response["id"].as_i64().unwrap_or(0));
+        assert_eq!(responses.len(), 4, "{:?}", responses);
+        assert_eq!(responses[0]["id"], Value::Null);
+        assert_eq!(error_code(&responses[0]), -32600);
+        assert!(responses[1].get("result").is_some());
+        assert_eq!(error_code(&responses[2]), -32600);
+        assert_eq!(responses[2]["id"], 2);
+        assert!(responses[3].get("result").is_some());
+
+        // Over SSE the array arrives as one event
+        let mut response = client.post(json!([ping(4), ping(5)]), JSON_OR_SSE).await;
+        let events = read_events(&mut response, 1).await;
+        assert_eq!(data(&events[0]).as_array().unwrap().len(), 2);
+        assert_eq!(response.chunk().await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn batches_of_notifications_get_no_reply() {
+        let url = start().await;
+        let client = TestClient::initialize(&url, "2025-03-26").await;
+
+        let notification = json!({ "jsonrpc": "2.0", "method": "notifications/roots/list_changed" });
+        let response = client.post(json!([notification.clone(), notification]), JSON).await;
+        assert_eq!(response.status(), StatusCode::ACCEPTED);
+        assert_eq!(response.text().await.unwrap(), "");
+    }
+
+    #[tokio::test]
+    async fn initialize_must_not_be_batched() {
+        let url = start().await;
+        let initialize = json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "initialize",
+            "params": {
+                "protocolVersion": "2025-03-26",
+                "capabilities": {},
+                "clientInfo": { "name": "test", "version": "1.0" },
+            },
+        });
+
+        let anonymous = TestClient {
+            http: reqwest::Client::new(),
+            url: url.clone(),
+            session_id: None,
+        };
+        let response = anonymous.post(json!([initialize.clone()]), JSON).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let client = TestClient::initialize(&url, "2025-03-26").await;
+        let body = json_body(client.post(json!([initialize, ping(2)]), JSON).await).await;
+        let rejected = body
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|response| response["id"] == 1)
+            .unwrap();
+        assert_eq!(error_code(rejected), -32600);
+    }
+
+    fn call_slow(id: i64) -> Value {
+        json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "method": "tools/call",
+            "params": { "name": "slow", "arguments": {} },
+        })
+    }
+
+    fn cancel(id: i64) -> Value {
+        json!({
+            "jsonrpc": "2.0",
+            "method": "notifications/cancelled",
+            "params": { "requestId": id, "reason": "test" },
+        })
+    }
+
+    #[tokio::test]
+    async fn cancelling_a_call_completes_its_post() {
+        let url = start().await;
+        let client = Arc::new(TestClient::initialize(&url, "2025-06-18").await);
+
+        let caller = client.clone();
+        let json_call = tokio::spawn(async move { caller.post(call_slow(1), JSON).await });
+        let mut sse_call = client.post(call_slow(2), JSON_OR_SSE).await;
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        for id in [1, 2] {
+            let response = client.post(cancel(id), JSON).await;
+            assert_eq!(response.status(), StatusCode::ACCEPTED);
+        }
+
+        // No response is sent, but neither POST is left hanging
+        let response = tokio::time::timeout(Duration::from_secs(5), json_call)
+            .await
+            .expect("JSON POST did not complete")
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::ACCEPTED);
+        let end = tokio::time::timeout(Duration::from_secs(5), sse_call.chunk())
+            .await
+            .expect("SSE stream did not close");
+        assert_eq!(end.unwrap(), None);
+
+        // The session carries on
+        let body = json_body(client.post(ping(3), JSON).await).await;
+        assert_eq!(body["id"], 3);
+    }
+
+    #[tokio::test]
+    async fn unsupported_protocol_version_headers_are_rejected() {
+        let url = start().await;
+        let client = TestClient::initialize(&url, "2025-06-18").await;
+
+        let with_version = |version: &str| {
+            client
+                .request(reqwest::Method::POST)
+                .header(header::ACCEPT, JSON)
+                .header(header::CONTENT_TYPE, JSON)
+                .header(PROTOCOL_VERSION_HEADER, version)
+                .body(ping(1).to_string())
+                .send()
+        };
+        let response = with_version("1999-01-01").await.unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let response = with_version("2025-06-18").await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn protocol_version_headers_must_match_the_negotiated_version() {
+        let url = start().await;
+        let client = TestClient::initialize(&url, "2025-03-26").await;
+
+        // Supported, but not what this session agreed on
+        let response = client
+            .request(reqwest::Method::POST)
+            .header(header::ACCEPT, JSON)
+            .header(header::CONTENT_TYPE, JSON)
+            .header(PROTOCOL_VERSION_HEADER, "2025-06-18")
+            .body(ping(1).to_string())
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let response = client
+            .request(reqwest::Method::GET)
+            .header(header::ACCEPT, "text/event-stream")
+            .header(PROTOCOL_VERSION_HEADER, "2025-06-18")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let response = client
+            .request(reqwest::Method::POST)
+            .header(header::ACCEPT, JSON)
+            .header(header::CONTENT_TYPE, JSON)
+            .header(PROTOCOL_VERSION_HEADER, "2025-03-26")
+            .body(ping(2).to_string())
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn tool_logs_reach_only_their_own_session() {
+        let url = start().await;
+        let a = TestClient::initialize(&url, "2025-06-18").await;
+        let b = TestClient::initialize(&url, "2025-06-18").await;
+        let mut a_stream = a.get(None).await;
+        let mut b_stream = b.get(None).await;
+
+        let log = |text: &str| {
+            json!({
+                "jsonrpc": "2.0",
+                "id": 1,
+                "method": "tools/call",
+                "params": { "name": "log", "arguments": { "text": text } },
+            })
+        };
+        let calls = [(&a, "a1"), (&b, "b1"), (&a, "a2"), (&b, "b2"), (&a, "end"), (&b, "end")];
+        for (client, text) in calls {
+            let response = json_body(client.post(log(text), JSON).await).await;
+            assert!(response.get("result").is_some(), "{}", response);
+        }
+
+        // Anything leaked from the other session would arrive before "end"
+        let streams = [
+            (&mut a_stream, ["a1", "a2", "end"]),
+            (&mut b_stream, ["b1", "b2", "end"]),
+        ];
+        for (stream, expected) in streams {
+            let logs: Vec<Value> = read_events(stream, 3)
+                .await
+                .iter()
+                .map(|event| data(event)["params"]["data"].clone())
+                .collect();
+            assert_eq!(logs, expected);
+        }
+    }
+}
@@ -0,0 +1,140 @@
+use std::error::Error;
+use std::sync::mpsc::{Receiver, Sender, channel};
+
+use super::{Transport, TransportReceiver, TransportSender};
+
+/// One end of an in-process connection, useful for wiring a client directly
+/// to a server without spawning a process.
+pub struct MemoryTransport {
+    pub sender: MemorySender,
+    pub receiver: MemoryReceiver,
+}
+
+pub struct MemorySender {
+    pub sender: Option<Sender<String>>,
+}
+
+pub struct MemoryReceiver {
+    pub receiver: Receiver<String>,
+}
+
+impl MemoryTransport {
+    /// Creates two connected transports; messages sent on one are received on the other.
+    pub fn pair() -> (MemoryTransport, MemoryTransport) {
+        let (a_sender, b_receiver) = channel();
+        let (b_sender, a_receiver) = channel();
+        (
+            MemoryTransport {
+                sender: MemorySender {
+                    sender: Some(a_sender),
+                },
+                receiver: MemoryReceiver {
+                    receiver: a_receiver,
+                },
+            },
+            MemoryTransport {
+                sender: MemorySender {
+                    sender: Some(b_sender),
+                },
+                receiver: MemoryReceiver {
+                    receiver: b_receiver,
+                },
+            },
+        )
+    }
+}
+
+impl TransportSender for MemorySender {
+    fn send(&mut self, message: &str) -> Result<(), Box<dyn Error>> {
+        let sender = self.sender.as_ref().ok_or("Transport is closed")?;
+        sender
+            .send(message.to_string())
+            .map_err(|_| "Peer transport is closed")?;
+        Ok(())
+    }
+
+    fn close(&mut self) -> Result<(), Box<dyn Error>> {
+        self.sender = None;
+        Ok(())
+    }
+}
+
+impl TransportReceiver for MemoryReceiver {
+    fn receive(&mut self) -> Result<Option<String>, Box<dyn Error>> {
+        // A disconnected channel means the peer closed or was dropped
+        Ok(self.receiver.recv().ok())
+    }
+}
+
+impl Transport for MemoryTransport {
+    fn send(&mut self, message: &str) -> Result<(), Box<dyn Error>> {
+        self.sender.send(message)
+    }
+
+    fn receive(&mut self) -> Result<Option<String>, Box<dyn Error>> {
+        self.receiver.receive()
+    }
+
+    fn close(&mut self) -> Result<(), Box<dyn Error>> {
+        self.sender.close()
+    }
+
+    fn split(self: Box<Self>) -> (Box<dyn TransportSender>, Box<dyn TransportReceiver>) {
+        (Box::new(self.sender), Box::new(self.receiver))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::Client;
+    use crate::server::Server;
+    use crate::types::{
+        Tool, ToolContext, ToolImplementation, ToolsCallResult, success_content, text_content,
+    };
+    use serde_json::json;
+
+    struct Echo;
+
+    impl ToolImplementation for Echo {
+        fn get_tool(&self) -> Tool {
+            Tool {
+                name: "echo".to_string(),
+                description: "Echoes its text".to_string(),
+                input_schema: json!({ "type": "object" }),
+            }
+        }
+
+        fn call(&self, arguments: serde_json::Value, _context: ToolContext) -> ToolsCallResult {
+            let text = arguments["text"].as_str().unwrap_or_default();
+            success_content(vec![text_content(text.to_string())])
+        }
+    }
+
+    #[tokio::test]
+    async fn client_and_server_talk_over_a_pair() {
+        let (client_end, server_end) = MemoryTransport::pair();
+        let server = Server::builder("memory", "1.0")
+            .tool(Box::new(Echo))
+            .transport(Box::new(server_end))
+            .build();
+        let serving = tokio::spawn(server.serve());
+
+        let mut client = Client::connect(Box::new(client_end)).await.unwrap();
+        assert_eq!(client.server_info().unwrap().name, "memory");
+
+        let tools = client.list_tools(None).await.unwrap();
+        assert_eq!(tools.tools.len(), 1);
+        assert_eq!(tools.tools[0].name, "echo");
+
+        let result = client
+            .call_tool("echo".to_string(), json!({ "text": "hi" }))
+            .await
+            .unwrap();
+        assert!(!result.is_error);
+        assert_eq!(result.content[0].content["text"], "hi");
+
+        client.close().await.unwrap();
+        serving.await.unwrap().unwrap();
+    }
+}
@@ -0,0 +1,67 @@
+/// A single Server-Sent Event.
+#[derive(Debug, Default, Clone)]
+pub struct SseEvent {
+    /// Event type; None means the default `message` type.
+    pub event: Option<String>,
+    pub data: String,
+    pub id: Option<String>,
+    /// Reconnection delay requested by the server, in milliseconds.
+    pub retry: Option<u64>,
+}
+
+/// Incremental parser for a `text/event-stream` body.
+#[derive(Default)]
+pub struct SseParser {
+    buffer: Vec<u8>,
+    event: SseEvent,
+    has_data: bool,
+}
+
+impl SseParser {
+    /// Feeds a chunk of the body and returns every event it completes.
+    /// Chunks may split lines, and even UTF-8 characters, anywhere.
+    pub fn push(&mut self, chunk: &[u8]) -> Vec<SseEvent> {
+        self.buffer.extend_from_slice(chunk);
+        let mut events = Vec::new();
+        while let Some(end) = self.buffer.iter().position(|&b| b == b'\n') {
+            let line: Vec<u8> = self.buffer.drain(..=end).collect();
+            let line = String::from_utf8_lossy(&line);
+            let line = line.trim_end_matches(['\n', '\r']);
+            if let Some(event) = self.process_line(line) {
+                events.push(event);
+            }
+        }
+        events
+    }
+
+    fn process_line(&mut self, line: &str) -> Option<SseEvent> {
+        // A blank line dispatches the event being built
+        if line.is_empty() {
+            let event = std::mem::take(&mut self.event);
+            let has_data = std::mem::take(&mut self.has_data);
+            return (has_data || event.id.is_some() || event.retry.is_some()).then_some(event);
+        }
+        if line.starts_with(':') {
+            return None; // Comment, used for keep-alives
+        }
+
+        let (field, value) = match line.split_once(':') {
+            Some((field, value)) => (field, value.strip_prefix(' ').unwrap_or(value)),
+            None => (line, ""),
+        };
+        match field {
+            "event" => self.event.event = Some(value.to_string()),
+            "data" => {
+                if self.has_data {
+                    self.event.data.push('\n');
+                }
+                self.event.data.push_str(value);
+                self.has_data = true;
+            }
+            "id" => self.event.id = Some(value.to_string()),
+            "retry" => self.event.retry = value.parse().ok(),
+            _ => {}
+        }
+        None
+    }
+}
@@ -0,0 +1,148 @@
+use reqwest::header::{ACCEPT, CONTENT_TYPE};
+use std::error::Error;
+use std::time::Duration;
+use tokio::runtime::Runtime;
+use tokio::sync::mpsc::unbounded_channel;
+use tokio::sync::oneshot;
+use tokio::task::JoinHandle;
+
+use super::http_client::HttpReceiver;
+use super::sse::SseParser;
+use super::{Transport, TransportReceiver, TransportSender};
+
+/// How long to wait for the server's `endpoint` event after connecting.
+const ENDPOINT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Connects to an MCP server over the legacy HTTP+SSE transport from protocol
+/// version 2024-11-05: messages from the server arrive on a long-lived SSE
+/// stream, and messages to it are POSTed to the endpoint the stream announces.
+pub struct LegacySseClientTransport {
+    pub sender: LegacySseSender,
+    pub receiver: HttpReceiver,
+}
+
+/// POSTs messages to the announced endpoint and owns the SSE reading task.
+pub struct LegacySseSender {
+    runtime: Runtime,
+    http: reqwest::Client,
+    endpoint: reqwest::Url,
+    stream_task: JoinHandle<()>,
+    closed: bool,
+}
+
+impl LegacySseClientTransport {
+    /// Opens the SSE stream at `url` and waits for the server to announce its
+    /// message endpoint.
+    pub fn connect(url: &str) -> Result<Self, Box<dyn Error>> {
+        let runtime = tokio::runtime::Builder::new_multi_thread()
+            .worker_threads(1)
+            .enable_all()
+            .build()?;
+        let http = reqwest::Client::new();
+        let sse_url = reqwest::Url::parse(url)?;
+
+        let response = runtime.block_on(
+            http.get(sse_url.clone())
+                .header(ACCEPT, "text/event-stream")
+                .send(),
+        )?;
+        if !response.status().is_success() {
+            return Err(format!("SSE connection failed with status {}", response.status()).into());
+        }
+
+        let (endpoint_sender, endpoint_receiver) = oneshot::channel();
+        let (incoming_sender, incoming) = unbounded_channel();
+        let stream_task = runtime.spawn(async move {
+            let mut response = response;
+            let mut endpoint_sender = Some(endpoint_sender);
+            let mut parser = SseParser::default();
+            while let Ok(Some(chunk)) = response.chunk().await {
+                for event in parser.push(&chunk) {
+                    match event.event.as_deref() {
+                        Some("endpoint") => {
+                            if let Some(sender) = endpoint_sender.take() {
+                                let _ = sender.send(event.data);
+                            }
+                        }
+                        None | Some("message") => {
+                            if incoming_sender.send(event.data).is_err() {
+                                return;
+                            }
+                        }
+                        Some(other) => {
+                            eprintln!("Client: Ignoring unknown SSE event '{}'", other);
+                        }
+                    }
+                }
+            }
+            // Dropping the sender tells `receive` the connection is closed
+        });
+
+        let endpoint = runtime.block_on(async {
+            tokio::time::timeout(ENDPOINT_TIMEOUT, endpoint_receiver).await
+        });
+        let endpoint = match endpoint {
+            Ok(Ok(endpoint)) => sse_url.join(&endpoint)?,
+            Ok(Err(_)) => return Err("SSE stream closed before the endpoint event".into()),
+            Err(_) => return Err("Timed out waiting for the SSE endpoint event".into()),
+        };
+
+        Ok(LegacySseClientTransport {
+            sender: LegacySseSender {
+                runtime,
+                http,
+                endpoint,
+                stream_task,
+                closed: false,
+            },
+            receiver: HttpReceiver { incoming },
+        })
+    }
+}
+
+impl TransportSender for LegacySseSender {
+    fn send(&mut self, message: &str) -> Result<(), Box<dyn Error>> {
+        if self.closed {
+            return Err("Transport is closed".into());
+        }
+        let response = self.runtime.block_on(
+            self.http
+                .post(self.endpoint.clone())
+                .header(CONTENT_TYPE, "application/json")
+                .body(message.to_string())
+                .send(),
+        )?;
+        if !response.status().is_success() {
+            return Err(format!("HTTP request failed with status {}", response.status()).into());
+        }
+        Ok(())
+    }
+
+    fn close(&mut self) -> Result<(), Box<dyn Error>> {
+        // Closing the SSE stream ends the session on the server
+        self.closed = true;
+        self.stream_task.abort();
+        Ok(())
+    }
+}
+
+impl Transport for LegacySseClientTransport {
+    fn send(&mut self, message: &str) -> Result<(), Box<dyn Error>> {
+        self.sender.send(message)
+    }
+
+    fn receive(&mut self) -> Result<Option<String>, Box<dyn Error>> {
+        if self.sender.closed {
+            return Ok(None);
+        }
+        self.receiver.receive()
+    }
+
+    fn close(&mut self) -> Result<(), Box<dyn Error>> {
+        self.sender.close()
+    }
+
+    fn split(self: Box<Self>) -> (Box<dyn TransportSender>, Box<dyn TransportReceiver>) {
+        (Box::new(self.sender), Box::new(self.receiver))
+    }
+}
@@ -0,0 +1,274 @@
+use axum::Router;
+use axum::extract::{Query, State};
+use axum::http::{HeaderMap, StatusCode};
+use axum::response::sse::{Event, KeepAlive, Sse};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::error::Error;
+use std::sync::{Arc, Mutex};
+use tokio::sync::mpsc::{UnboundedReceiver, UnboundedSender, unbounded_channel};
+use tokio_stream::StreamExt;
+use tokio_stream::wrappers::UnboundedReceiverStream;
+
+use super::http_server::check_origin;
+use super::{Transport, TransportReceiver, TransportSender};
+
+type RunSession = dyn Fn(Box<dyn Transport>) -> Result<(), Box<dyn Error>> + Send + Sync;
+
+/// Serves MCP over the legacy HTTP+SSE transport from protocol version 2024-11-05.
+///
+/// A client opens an SSE stream with GET `sse_path` and is told, in an
+/// `endpoint` event, where to POST its messages (`message_path?sessionId=...`).
+/// Every message from the server, responses included, arrives on the SSE
+/// stream. Each stream is a session whose transport is handed to `run_session`
+/// on a dedicated thread; closing the stream ends the session.
+/// Blocks until the listener fails.
+pub fn serve<F, E>(
+    addr: &str,
+    sse_path: &str,
+    message_path: &str,
+    run_session: F,
+) -> Result<(), Box<dyn Error>>
+where
+    F: Fn(Box<dyn Transport>) -> Result<(), E> + Send + Sync + 'static,
+    E: Into<Box<dyn Error>>,
+{
+    let app = router(sse_path, message_path, run_session);
+    let runtime = tokio::runtime::Runtime::new()?;
+    runtime.block_on(async {
+        let listener = tokio::net::TcpListener::bind(addr).await?;
+        eprintln!(
+            "Server: Listening for HTTP+SSE on http://{}{}",
+            listener.local_addr()?,
+            sse_path
+        );
+        axum::serve(listener, app).await
+    })?;
+    Ok(())
+}
+
+/// Routes the SSE stream at `sse_path` and the message endpoint at
+/// `message_path` to sessions run by `run_session`.
+fn router<F, E>(sse_path: &str, message_path: &str, run_session: F) -> Router
+where
+    F: Fn(Box<dyn Transport>) -> Result<(), E> + Send + Sync + 'static,
+    E: Into<Box<dyn Error>>,
+{
+    let state = Arc::new(SseServerState {
+        run_session: Arc::new(move |transport| run_session(transport).map_err(Into::into)),
+        message_path: message_path.to_string(),
+        sessions: Mutex::new(HashMap::new()),
+    });
+    Router::new()
+        .route(sse_path, get(handle_sse))
+        .route(message_path, post(handle_message))
+        .with_state(state)
+}
+
+struct SseServerState {
+    run_session: Arc<RunSession>,
+    message_path: String,
+    /// Maps a session id to the channel feeding that session.
+    sessions: Mutex<HashMap<String, UnboundedSender<String>>>,
+}
+
+/// Ends its session when the SSE stream holding it is dropped.
+struct SessionGuard {
+    state: Arc<SseServerState>,
+    id: String,
+}
+
+impl Drop for SessionGuard {
+    fn drop(&mut self) {
+        if self.state.sessions.lock().unwrap().remove(&self.id).is_some() {
+            eprintln!("Server: Session {} closed", self.id);
+        }
+    }
+}
+
+/// The Transport handed to a session.
+struct SessionTransport {
+    sender: SessionSender,
+    receiver: SessionReceiver,
+}
+
+struct SessionSender {
+    outgoing: Option<UnboundedSender<String>>,
+}
+
+struct SessionReceiver {
+    incoming: UnboundedReceiver<String>,
+}
+
+impl TransportSender for SessionSender {
+    fn send(&mut self, message: &str) -> Result<(), Box<dyn Error>> {
+        let outgoing = self.outgoing.as_ref().ok_or("Transport is closed")?;
+        outgoing
+            .send(message.to_string())
+            .map_err(|_| "Client closed the SSE stream")?;
+        Ok(())
+    }
+
+    fn close(&mut self) -> Result<(), Box<dyn Error>> {
+        // Dropping the sender ends the SSE stream, which ends the session
+        self.outgoing = None;
+        Ok(())
+    }
+}
+
+impl TransportReceiver for SessionReceiver {
+    fn receive(&mut self) -> Result<Option<String>, Box<dyn Error>> {
+        Ok(self.incoming.blocking_recv())
+    }
+}
+
+impl Transport for SessionTransport {
+    fn send(&mut self, message: &str) -> Result<(), Box<dyn Error>> {
+        self.sender.send(message)
+    }
+
+    fn receive(&mut self) -> Result<Option<String>, Box<dyn Error>> {
+        self.receiver.receive()
+    }
+
+    fn close(&mut self) -> Result<(), Box<dyn Error>> {
+        self.sender.close()
+    }
+
+    fn split(self: Box<Self>) -> (Box<dyn TransportSender>, Box<dyn TransportReceiver>) {
+        (Box::new(self.sender), Box::new(self.receiver))
+    }
+}
+
+async fn handle_sse(State(state): State<Arc<SseServerState>>, headers: HeaderMap) -> Response {
+    if let Err(rejection) = check_origin(&headers) {
+        return rejection.into_response();
+    }
+
+    let id = uuid::Uuid::new_v4().to_string();
+    let (incoming_sender, incoming) = unbounded_channel();
+    let (outgoing, outgoing_receiver) = unbounded_channel();
+    state
+        .sessions
+        .lock()
+        .unwrap()
+        .insert(id.clone(), incoming_sender);
+
+    let transport = SessionTransport {
+        sender: SessionSender {
+            outgoing: Some(outgoing),
+        },
+        receiver: SessionReceiver { incoming },
+    };
+    let run_session = state.run_session.clone();
+    let session_id = id.clone();
+    std::thread::spawn(move || {
+        eprintln!("Server: Session {} started", session_id);
+        if let Err(e) = run_session(Box::new(transport)) {
+            eprintln!("Server: Session {} ended with error: {}", session_id, e);
+        }
+    });
+
+    let endpoint = Event::default()
+        .event("endpoint")
+        .data(format!("{}?sessionId={}", state.message_path, id));
+    let guard = SessionGuard { state, id };
+    let messages = UnboundedReceiverStream::new(outgoing_receiver).map(move |message| {
+        let _session = &guard;
+        Event::default().event("message").data(message)
+    });
+    let stream = tokio_stream::once(endpoint)
+        .chain(messages)
+        .map(Ok::<_, Infallible>);
+
+    Sse::new(stream)
+        .keep_alive(KeepAlive::default())
+        .into_response()
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct MessageQuery {
+    session_id: String,
+}
+
+async fn handle_message(
+    State(state): State<Arc<SseServerState>>,
+    Query(query): Query<MessageQuery>,
+    headers: HeaderMap,
+    body: String,
+) -> Response {
+    if let Err(rejection) = check_origin(&headers) {
+        return rejection.into_response();
+    }
+
+    let sessions = state.sessions.lock().unwrap();
+    let Some(sender) = sessions.get(&query.session_id) else {
+        return (StatusCode::NOT_FOUND, "Unknown session").into_response();
+    };
+    if sender.send(body).is_err() {
+        return (StatusCode::NOT_FOUND, "Session has ended").into_response();
+    }
+    (StatusCode::ACCEPTED, "Accepted").into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::Server;
+    use crate::transport::LegacySseClientTransport;
+    use serde_json::{Value, json};
+
+    /// Serves a test server on an ephemeral port from its own thread and
+    /// returns the URL of its SSE stream.
+    fn start() -> String {
+        let (address_sender, address) = std::sync::mpsc::channel();
+        std::thread::spawn(move || {
+            let app = router("/sse", "/messages", |transport| {
+                Server::builder("test", "1.0").transport(transport).build().run()
+            });
+            let runtime = tokio::runtime::Runtime::new().unwrap();
+            runtime.block_on(async {
+                let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+                address_sender.send(listener.local_addr().unwrap()).unwrap();
+                axum::serve(listener, app).await.unwrap();
+            });
+        });
+        format!("http://{}/sse", address.recv().unwrap())
+    }
+
+    #[test]
+    fn responses_to_posted_requests_arrive_on_the_stream() {
+        // Connecting waits for the endpoint event, so sends reach the session
+        let mut transport = LegacySseClientTransport::connect(&start()).unwrap();
+
+        let initialize = json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "initialize",
+            "params": {
+                "protocolVersion": "2024-11-05",
+                "capabilities": {},
+                "clientInfo": { "name": "test", "version": "1.0" },
+            },
+        });
+        transport.send(&initialize.to_string()).unwrap();
+        let response: Value = serde_json::from_str(&transport.receive().unwrap().unwrap()).unwrap();
+        assert_eq!(response["id"], 1);
+        assert_eq!(response["result"]["protocolVersion"], "2024-11-05");
+        assert_eq!(response["result"]["serverInfo"]["name"], "test");
+
+        transport
+            .send(&json!({ "jsonrpc": "2.0", "method": "notifications/initialized" }).to_string())
+            .unwrap();
+        transport
+            .send(&json!({ "jsonrpc": "2.0", "id": 2, "method": "ping" }).to_string())
+            .unwrap();
+        let response: Value = serde_json::from_str(&transport.receive().unwrap().unwrap()).unwrap();
+        assert_eq!(response, json!({ "jsonrpc": "2.0", "result": {}, "id": 2 }));
+        transport.close().unwrap();
+    }
+}
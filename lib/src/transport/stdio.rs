@@ -0,0 +1,203 @@
+use std::error::Error;
+use std::io::{self, BufReader};
+use std::process::{Child, ChildStdin, ChildStdout, Command, Stdio};
+use std::sync::{Arc, Mutex};
+
+use super::framing::{self, Framing};
+use super::{Transport, TransportReceiver, TransportSender};
+
+/// Talks to a spawned child process over its stdin and stdout.
+pub struct ChildStdioTransport {
+    pub sender: ChildStdinSender,
+    pub receiver: ChildStdoutReceiver,
+}
+
+/// Writes messages to a child's stdin. Owns the child so closing can reap it.
+pub struct ChildStdinSender {
+    pub child: Child,
+    pub stdin: Option<ChildStdin>,
+    pub framing: Framing,
+}
+
+/// Reads messages from a child's stdout.
+pub struct ChildStdoutReceiver {
+    pub reader: BufReader<ChildStdout>,
+    pub framing: Framing,
+}
+
+impl ChildStdioTransport {
+    /// Spawns the command with piped stdin and stdout. Stderr is inherited.
+    pub fn spawn(mut command: Command) -> Result<Self, Box<dyn Error>> {
+        let mut child = command
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()?;
+
+        let stdin = child.stdin.take().ok_or("Failed to take stdin")?;
+        let stdout = child.stdout.take().ok_or("Failed to take stdout")?;
+
+        Ok(ChildStdioTransport {
+            sender: ChildStdinSender {
+                child,
+                stdin: Some(stdin),
+                framing: Framing::default(),
+            },
+            receiver: ChildStdoutReceiver {
+                reader: BufReader::new(stdout),
+                framing: Framing::default(),
+            },
+        })
+    }
+
+    /// Uses the given framing instead of newline-delimited JSON.
+    pub fn with_framing(mut self, framing: Framing) -> Self {
+        self.sender.framing = framing;
+        self.receiver.framing = framing;
+        self
+    }
+}
+
+impl TransportSender for ChildStdinSender {
+    fn send(&mut self, message: &str) -> Result<(), Box<dyn Error>> {
+        let stdin = self.stdin.as_mut().ok_or("Transport is closed")?;
+        framing::write_message(stdin, message, self.framing)
+    }
+
+    fn close(&mut self) -> Result<(), Box<dyn Error>> {
+        // Closing stdin signals EOF; a well-behaved server exits on its own
+        if self.stdin.take().is_some() {
+            self.child.wait()?;
+        }
+        Ok(())
+    }
+}
+
+impl TransportReceiver for ChildStdoutReceiver {
+    fn receive(&mut self) -> Result<Option<String>, Box<dyn Error>> {
+        framing::read_message(&mut self.reader, self.framing)
+    }
+}
+
+impl Transport for ChildStdioTransport {
+    fn send(&mut self, message: &str) -> Result<(), Box<dyn Error>> {
+        self.sender.send(message)
+    }
+
+    fn receive(&mut self) -> Result<Option<String>, Box<dyn Error>> {
+        self.receiver.receive()
+    }
+
+    fn close(&mut self) -> Result<(), Box<dyn Error>> {
+        self.sender.close()
+    }
+
+    fn split(self: Box<Self>) -> (Box<dyn TransportSender>, Box<dyn TransportReceiver>) {
+        (Box::new(self.sender), Box::new(self.receiver))
+    }
+}
+
+/// Serves the current process's own stdin and stdout.
+pub struct StdioTransport {
+    pub sender: StdoutSender,
+    pub receiver: StdinReceiver,
+}
+
+/// Framing shared by both halves of a [`StdioTransport`]. When None, it is
+/// detected from the first message received and then used for replies, so
+/// older Content-Length peers keep working.
+type SharedFraming = Arc<Mutex<Option<Framing>>>;
+
+/// Writes messages to this process's stdout.
+pub struct StdoutSender {
+    pub writer: io::Stdout,
+    pub framing: SharedFraming,
+    pub closed: bool,
+}
+
+/// Reads messages from this process's stdin.
+pub struct StdinReceiver {
+    pub reader: BufReader<io::Stdin>,
+    pub framing: SharedFraming,
+}
+
+impl StdioTransport {
+    pub fn new() -> Self {
+        let framing = SharedFraming::default();
+        StdioTransport {
+            sender: StdoutSender {
+                writer: io::stdout(),
+                framing: framing.clone(),
+                closed: false,
+            },
+            receiver: StdinReceiver {
+                reader: BufReader::new(io::stdin()),
+                framing,
+            },
+        }
+    }
+
+    /// Uses the given framing instead of detecting it from incoming messages.
+    pub fn with_framing(self, framing: Framing) -> Self {
+        *self.sender.framing.lock().unwrap() = Some(framing);
+        self
+    }
+}
+
+impl Default for StdioTransport {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TransportSender for StdoutSender {
+    fn send(&mut self, message: &str) -> Result<(), Box<dyn Error>> {
+        if self.closed {
+            return Err("Transport is closed".into());
+        }
+        let framing = self.framing.lock().unwrap().unwrap_or_default();
+        framing::write_message(&mut self.writer, message, framing)
+    }
+
+    fn close(&mut self) -> Result<(), Box<dyn Error>> {
+        self.closed = true;
+        Ok(())
+    }
+}
+
+impl TransportReceiver for StdinReceiver {
+    fn receive(&mut self) -> Result<Option<String>, Box<dyn Error>> {
+        let configured = *self.framing.lock().unwrap();
+        match configured {
+            Some(framing) => framing::read_message(&mut self.reader, framing),
+            None => {
+                let Some((message, framing)) = framing::read_message_detect(&mut self.reader)?
+                else {
+                    return Ok(None);
+                };
+                *self.framing.lock().unwrap() = Some(framing);
+                Ok(Some(message))
+            }
+        }
+    }
+}
+
+impl Transport for StdioTransport {
+    fn send(&mut self, message: &str) -> Result<(), Box<dyn Error>> {
+        self.sender.send(message)
+    }
+
+    fn receive(&mut self) -> Result<Option<String>, Box<dyn Error>> {
+        if self.sender.closed {
+            return Ok(None);
+        }
+        self.receiver.receive()
+    }
+
+    fn close(&mut self) -> Result<(), Box<dyn Error>> {
+        self.sender.close()
+    }
+
+    fn split(self: Box<Self>) -> (Box<dyn TransportSender>, Box<dyn TransportReceiver>) {
+        (Box::new(self.sender), Box::new(self.receiver))
+    }
+}
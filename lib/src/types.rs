@@ -1,6 +1,14 @@
+use async_trait::async_trait;
+use base64::Engine;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::sync::broadcast;
+use tokio::sync::mpsc::UnboundedSender;
+use tokio_util::sync::CancellationToken;
 
-use crate::jsonrpc::JsonRpcResponse;
+use crate::jsonrpc::{JsonRpcError, JsonRpcNotification, RequestId};
+use crate::logging::Logger;
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct AddParams {
@@ -8,7 +16,7 @@ pub struct AddParams {
     pub b: i64,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Tool {
     pub name: String,
@@ -34,6 +42,34 @@ pub struct ToolsListResult {
 pub struct ToolsCallParams {
     pub name: String,
     pub arguments: serde_json::Value,
+    #[serde(rename = "_meta", default, skip_serializing_if = "Option::is_none")]
+    pub meta: Option<RequestMeta>,
+}
+
+/// Progress tokens take the same forms as request ids: a string or a number.
+pub type ProgressToken = RequestId;
+
+/// The `_meta` object a request may carry alongside its params.
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RequestMeta {
+    /// Asks the receiver to report progress on the request under this token.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub progress_token: Option<ProgressToken>,
+}
+
+/// Params of a `notifications/progress` notification.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProgressParams {
+    pub progress_token: ProgressToken,
+    /// How far along the work is. Increases with each notification.
+    pub progress: f64,
+    /// The value `progress` reaches when done, if known.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub total: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -52,13 +88,135 @@ pub struct ToolsCallResult {
     pub is_error: bool,
 }
 
+/// Params of a `notifications/cancelled` notification, sent by either side to
+/// abandon a request it sent earlier.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CancelledParams {
+    pub request_id: RequestId,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+}
+
+/// What a tool call can know about the request it serves besides its arguments.
+#[derive(Clone, Default)]
+pub struct ToolContext {
+    cancellation: CancellationToken,
+    /// The client's progress token, and where to send progress notifications.
+    progress: Option<(ProgressToken, UnboundedSender<String>)>,
+    logger: Logger,
+}
+
+impl ToolContext {
+    pub(crate) fn new(
+        cancellation: CancellationToken,
+        progress: Option<(ProgressToken, UnboundedSender<String>)>,
+        logger: Logger,
+    ) -> Self {
+        ToolContext {
+            cancellation,
+            progress,
+            logger,
+        }
+    }
+
+    /// Sends log messages to the client that made this call, and no other.
+    pub fn logger(&self) -> &Logger {
+        &self.logger
+    }
+
+    /// The token the client asked progress to be reported under, if any.
+    pub fn progress_token(&self) -> Option<&ProgressToken> {
+        self.progress.as_ref().map(|(token, _)| token)
+    }
+
+    /// Sends the client a `notifications/progress` for this call. `progress`
+    /// should increase with each report. Does nothing if the client didn't ask
+    /// for progress or has cancelled the request.
+    pub fn report_progress(&self, progress: f64, total: Option<f64>, message: Option<String>) {
+        let Some((token, outgoing)) = &self.progress else {
+            return;
+        };
+        if self.is_cancelled() {
+            return;
+        }
+        let notification = JsonRpcNotification {
+            jsonrpc: "2.0".to_string(),
+            method: "notifications/progress".to_string(),
+            params: Some(ProgressParams {
+                progress_token: token.clone(),
+                progress,
+                total,
+                message,
+            }),
+        };
+        if let Ok(message) = serde_json::to_string(&notification) {
+            let _ = outgoing.send(message);
+        }
+    }
+
+    /// Returns true once the client has cancelled the request. Long-running
+    /// synchronous tools should check this and stop early; the client no
+    /// longer waits for their result.
+    pub fn is_cancelled(&self) -> bool {
+        self.cancellation.is_cancelled()
+    }
+
+    /// Resolves once the client has cancelled the request.
+    pub async fn cancelled(&self) {
+        self.cancellation.cancelled().await
+    }
+}
+
 /// Trait for implementing individual tools
-pub trait ToolImplementation {
+pub trait ToolImplementation: Send + Sync {
     /// Get the tool definition (name, description, schema)
     fn get_tool(&self) -> Tool;
-    
+
     /// Execute the tool with the given arguments
-    fn call(&self, arguments: serde_json::Value) -> ToolsCallResult;
+    fn call(&self, arguments: serde_json::Value, context: ToolContext) -> ToolsCallResult;
+}
+
+/// Trait for tools that do their work asynchronously. The server runs calls
+/// concurrently, so a slow tool doesn't hold up other requests.
+#[async_trait]
+pub trait AsyncToolImplementation: Send + Sync {
+    /// Get the tool definition (name, description, schema)
+    fn get_tool(&self) -> Tool;
+
+    /// Execute the tool with the given arguments. If the client cancels the
+    /// request, the returned future is dropped at its next await point.
+    async fn call(&self, arguments: serde_json::Value, context: ToolContext) -> ToolsCallResult;
+
+    /// Like [`call`](Self::call), but may fail the whole request with a JSON-RPC
+    /// error instead of returning an error result. The server calls this one;
+    /// tools relaying calls elsewhere override it to pass errors on unchanged.
+    async fn try_call(
+        &self,
+        arguments: serde_json::Value,
+        context: ToolContext,
+    ) -> Result<ToolsCallResult, JsonRpcError> {
+        Ok(self.call(arguments, context).await)
+    }
+}
+
+/// Adapts a synchronous [`ToolImplementation`] to [`AsyncToolImplementation`].
+/// Calls run on tokio's blocking thread pool so they can block freely.
+pub struct SyncTool(pub Arc<dyn ToolImplementation>);
+
+#[async_trait]
+impl AsyncToolImplementation for SyncTool {
+    fn get_tool(&self) -> Tool {
+        self.0.get_tool()
+    }
+
+    async fn call(&self, arguments: serde_json::Value, context: ToolContext) -> ToolsCallResult {
+        let tool = self.0.clone();
+        match tokio::task::spawn_blocking(move || tool.call(arguments, context)).await {
+            Ok(result) => result,
+            Err(e) => error_content(format!("Tool failed: {}", e)),
+        }
+    }
 }
 
 /// Helper function to create text content
@@ -84,3 +242,270 @@ pub fn success_content(content: Vec<ToolContent>) -> ToolsCallResult {
         is_error: false,
     }
 }
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Resource {
+    pub uri: String,
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mime_type: Option<String>,
+}
+
+/// Describes a family of resources by an RFC 6570 URI template, e.g. `file:///{path}`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ResourceTemplate {
+    pub uri_template: String,
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mime_type: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ResourcesListParams {
+    pub cursor: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ResourcesListResult {
+    pub resources: Vec<Resource>,
+    pub next_cursor: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ResourceTemplatesListParams {
+    pub cursor: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ResourceTemplatesListResult {
+    pub resource_templates: Vec<ResourceTemplate>,
+    pub next_cursor: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ResourcesReadParams {
+    pub uri: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ResourcesReadResult {
+    pub contents: Vec<ResourceContents>,
+}
+
+/// The contents of a resource: text, or binary data encoded as base64.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged, rename_all_fields = "camelCase")]
+pub enum ResourceContents {
+    Text {
+        uri: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        mime_type: Option<String>,
+        text: String,
+    },
+    Blob {
+        uri: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        mime_type: Option<String>,
+        blob: String,
+    },
+}
+
+/// Trait for exposing resources to clients
+#[async_trait]
+pub trait ResourceProvider: Send + Sync {
+    /// List the concrete resources this provider exposes
+    async fn list(&self) -> Vec<Resource>;
+
+    /// URI templates for further resources this provider can read
+    fn templates(&self) -> Vec<ResourceTemplate> {
+        Vec::new()
+    }
+
+    /// Read a listed resource, or one matching a template. `variables` holds
+    /// the values matched from the template, and is empty for listed resources.
+    async fn read(
+        &self,
+        uri: &str,
+        variables: HashMap<String, String>,
+    ) -> Result<Vec<ResourceContents>, String>;
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ResourcesSubscribeParams {
+    pub uri: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ResourcesUnsubscribeParams {
+    pub uri: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ResourceUpdatedParams {
+    pub uri: String,
+}
+
+/// A change to the resources a server exposes.
+#[derive(Debug, Clone)]
+pub enum ResourceChange {
+    /// The contents of the resource at this URI changed.
+    Updated(String),
+    /// Resources were added or removed.
+    ListChanged,
+}
+
+/// Tells connected clients that resources changed. Clone it into whatever
+/// notices the change; every server session using it sends
+/// `notifications/resources/updated` to clients subscribed to the URI, and
+/// `notifications/resources/list_changed` to all clients.
+#[derive(Clone)]
+pub struct ResourceNotifier {
+    sender: broadcast::Sender<ResourceChange>,
+}
+
+/// Changes that can be queued for a session before older ones are dropped.
+const RESOURCE_CHANGE_CAPACITY: usize = 256;
+
+impl ResourceNotifier {
+    pub fn new() -> Self {
+        let (sender, _) = broadcast::channel(RESOURCE_CHANGE_CAPACITY);
+        ResourceNotifier { sender }
+    }
+
+    /// Reports that the contents of the resource at `uri` changed.
+    pub fn notify_updated(&self, uri: &str) {
+        // No receivers just means no sessions are running
+        let _ = self.sender.send(ResourceChange::Updated(uri.to_string()));
+    }
+
+    /// Reports that the list of resources changed.
+    pub fn notify_list_changed(&self) {
+        let _ = self.sender.send(ResourceChange::ListChanged);
+    }
+
+    pub(crate) fn subscribe(&self) -> broadcast::Receiver<ResourceChange> {
+        self.sender.subscribe()
+    }
+}
+
+impl Default for ResourceNotifier {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Helper function to create text resource contents
+pub fn text_resource(uri: &str, mime_type: Option<&str>, text: String) -> ResourceContents {
+    ResourceContents::Text {
+        uri: uri.to_string(),
+        mime_type: mime_type.map(str::to_string),
+        text,
+    }
+}
+
+/// Helper function to create binary resource contents, encoding the bytes as base64
+pub fn blob_resource(uri: &str, mime_type: Option<&str>, bytes: &[u8]) -> ResourceContents {
+    ResourceContents::Blob {
+        uri: uri.to_string(),
+        mime_type: mime_type.map(str::to_string),
+        blob: base64::engine::general_purpose::STANDARD.encode(bytes),
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Prompt {
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub arguments: Vec<PromptArgument>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PromptArgument {
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub required: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PromptsListParams {
+    pub cursor: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PromptsListResult {
+    pub prompts: Vec<Prompt>,
+    pub next_cursor: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PromptsGetParams {
+    pub name: String,
+    #[serde(default)]
+    pub arguments: HashMap<String, String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PromptsGetResult {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    pub messages: Vec<PromptMessage>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    User,
+    Assistant,
+}
+
+/// One message of a rendered prompt. Content has the same shape as tool
+/// content: text, image or an embedded resource.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PromptMessage {
+    pub role: Role,
+    pub content: ToolContent,
+}
+
+/// Trait for implementing prompt templates
+pub trait PromptImplementation: Send + Sync {
+    /// Get the prompt definition (name, description, arguments)
+    fn get_prompt(&self) -> Prompt;
+
+    /// Render the prompt's messages. The server has already checked that every
+    /// required argument is present and that no undeclared ones were given.
+    fn render(&self, arguments: HashMap<String, String>) -> Result<Vec<PromptMessage>, String>;
+}
+
+/// Helper function to create a text prompt message
+pub fn prompt_message(role: Role, text: String) -> PromptMessage {
+    PromptMessage {
+        role,
+        content: text_content(text),
+    }
+}
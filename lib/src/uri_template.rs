@@ -0,0 +1,182 @@
+use std::collections::HashMap;
+
+/// An RFC 6570 URI template, used to match resource URIs against resource
+/// templates. Supports the `{var}` and `{+var}` expressions resource templates
+/// use in practice: `{var}` matches within one path segment, `{+var}` matches
+/// anything, including slashes.
+#[derive(Debug, Clone)]
+pub struct UriTemplate {
+    parts: Vec<Part>,
+}
+
+#[derive(Debug, Clone)]
+enum Part {
+    Literal(String),
+    Variable { name: String, reserved: bool },
+}
+
+impl UriTemplate {
+    pub fn parse(template: &str) -> Result<Self, String> {
+        let mut parts = Vec::new();
+        let mut rest = template;
+        while let Some(start) = rest.find('{') {
+            if start > 0 {
+                parts.push(Part::Literal(rest[..start].to_string()));
+            }
+            let end = rest[start..]
+                .find('}')
+                .ok_or_else(|| format!("Unclosed expression in URI template '{}'", template))?;
+            let expression = &rest[start + 1..start + end];
+            let (name, reserved) = match expression.strip_prefix('+') {
+                Some(name) => (name, true),
+                None => (expression, false),
+            };
+            if name.is_empty() || !name.chars().all(|c| c.is_alphanumeric() || c == '_') {
+                return Err(format!(
+                    "Unsupported expression '{{{}}}' in URI template '{}'",
+                    expression, template
+                ));
+            }
+            parts.push(Part::Variable {
+                name: name.to_string(),
+                reserved,
+            });
+            rest = &rest[start + end + 1..];
+        }
+        if !rest.is_empty() {
+            parts.push(Part::Literal(rest.to_string()));
+        }
+        Ok(UriTemplate { parts })
+    }
+
+    /// Matches a URI against the template, returning the (percent-decoded)
+    /// value of each variable, or None if the URI doesn't fit.
+    pub fn matches(&self, uri: &str) -> Option<HashMap<String, String>> {
+        let mut variables = HashMap::new();
+        match_parts(&self.parts, uri, &mut variables).then_some(variables)
+    }
+}
+
+fn match_parts(parts: &[Part], uri: &str, variables: &mut HashMap<String, String>) -> bool {
+    match parts.split_first() {
+        None => uri.is_empty(),
+        Some((Part::Literal(literal), rest)) => uri
+            .strip_prefix(literal.as_str())
+            .is_some_and(|uri| match_parts(rest, uri, variables)),
+        Some((Part::Variable { name, reserved }, rest)) => {
+            let limit = if *reserved {
+                uri.len()
+            } else {
+                uri.find(['/', '?', '#']).unwrap_or(uri.len())
+            };
+            // Prefer the longest value, backtracking when the rest doesn't match
+            for end in (1..=limit).rev().filter(|&end| uri.is_char_boundary(end)) {
+                if match_parts(rest, &uri[end..], variables) {
+                    variables.insert(name.clone(), percent_decode(&uri[..end]));
+                    return true;
+                }
+            }
+            false
+        }
+    }
+}
+
+fn percent_decode(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes
+            .get(i + 1..i + 3)
+            .filter(|hex| hex.iter().all(u8::is_ascii_hexdigit))
+            .and_then(|hex| std::str::from_utf8(hex).ok());
+        match (bytes[i], hex.and_then(|hex| u8::from_str_radix(hex, 16).ok())) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            (byte, _) => {
+                decoded.push(byte);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matches(template: &str, uri: &str) -> Option<HashMap<String, String>> {
+        UriTemplate::parse(template).unwrap().matches(uri)
+    }
+
+    fn variables(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+        pairs.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect()
+    }
+
+    #[test]
+    fn literal_templates_match_only_themselves() {
+        assert_eq!(matches("hello://about", "hello://about"), Some(HashMap::new()));
+        assert_eq!(matches("hello://about", "hello://about/more"), None);
+        assert_eq!(matches("hello://about", "hello://abou"), None);
+    }
+
+    #[test]
+    fn multiple_variables_are_matched() {
+        assert_eq!(
+            matches("repo://{owner}/{name}/issues", "repo://rust-lang/rust/issues"),
+            Some(variables(&[("owner", "rust-lang"), ("name", "rust")]))
+        );
+    }
+
+    #[test]
+    fn simple_variables_stay_within_a_segment() {
+        assert_eq!(matches("file:///{name}", "file:///src/main.rs"), None);
+        assert_eq!(
+            matches("file:///{name}.rs", "file:///main.rs"),
+            Some(variables(&[("name", "main")]))
+        );
+    }
+
+    #[test]
+    fn reserved_variables_span_segments() {
+        assert_eq!(
+            matches("file:///{+path}", "file:///src/main.rs"),
+            Some(variables(&[("path", "src/main.rs")]))
+        );
+        // Backtracks so the literal after the variable still matches
+        assert_eq!(
+            matches("file:///{+path}/README", "file:///a/b/README"),
+            Some(variables(&[("path", "a/b")]))
+        );
+    }
+
+    #[test]
+    fn values_are_percent_decoded() {
+        assert_eq!(
+            matches("greeting://{name}", "greeting://J%C3%BCrgen%20M"),
+            Some(variables(&[("name", "Jürgen M")]))
+        );
+        // Stray percent signs are kept as they are
+        assert_eq!(
+            matches("greeting://{name}", "greeting://100%"),
+            Some(variables(&[("name", "100%")]))
+        );
+    }
+
+    #[test]
+    fn non_matching_uris_are_rejected() {
+        assert_eq!(matches("greeting://{name}", "farewell://ann"), None);
+        // Variables match at least one character
+        assert_eq!(matches("greeting://{name}", "greeting://"), None);
+    }
+
+    #[test]
+    fn unsupported_expressions_are_rejected() {
+        assert!(UriTemplate::parse("greeting://{name").is_err());
+        assert!(UriTemplate::parse("greeting://{#name}").is_err());
+        assert!(UriTemplate::parse("greeting://{}").is_err());
+    }
+}
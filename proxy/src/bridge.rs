@@ -0,0 +1,195 @@
+use lib::transport::{
+    ChildStdioTransport, LegacySseClientTransport, StdioTransport, StreamableHttpClientTransport,
+    Transport, TransportReceiver, TransportSender, http_server, sse_server,
+};
+use std::error::Error;
+use std::process::Command;
+
+pub const USAGE: &str = "Usage: proxy bridge [--sse] <url>
+       proxy bridge [--sse] [--listen <addr>] [--path <path>] -- <command> [args...]";
+
+/// Where the bridge connects to, and so which direction it runs in.
+enum Target {
+    /// A remote HTTP server, exposed on this process's stdio.
+    Url(String),
+    /// A local stdio server, exposed over HTTP. Spawned once per session.
+    Command(String, Vec<String>),
+}
+
+struct BridgeArgs {
+    target: Target,
+    /// Use the legacy HTTP+SSE transport instead of Streamable HTTP.
+    sse: bool,
+    listen: String,
+    path: Option<String>,
+}
+
+fn parse_args(args: impl Iterator<Item = String>) -> Result<BridgeArgs, String> {
+    let mut sse = false;
+    let mut listen = "127.0.0.1:8000".to_string();
+    let mut path = None;
+    let mut target = None;
+
+    let mut args = args.peekable();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--sse" => sse = true,
+            "--listen" => listen = args.next().ok_or("--listen requires a value")?,
+            "--path" => path = Some(args.next().ok_or("--path requires a value")?),
+            "--" => {
+                let command = args.next().ok_or("-- must be followed by a command")?;
+                target = Some(Target::Command(command, args.by_ref().collect()));
+            }
+            _ if arg.starts_with("http://") || arg.starts_with("https://") => {
+                if args.peek().is_some() {
+                    return Err("Unexpected arguments after the upstream URL".to_string());
+                }
+                target = Some(Target::Url(arg));
+            }
+            _ => return Err(format!("Unknown argument '{}'", arg)),
+        }
+    }
+
+    let target = target.ok_or("Expected an upstream URL or -- <command>")?;
+    Ok(BridgeArgs {
+        target,
+        sse,
+        listen,
+        path,
+    })
+}
+
+/// Runs `proxy bridge`, relaying raw JSON-RPC messages between stdio and HTTP.
+pub fn run(args: impl Iterator<Item = String>) -> Result<(), Box<dyn Error>> {
+    let args = match parse_args(args) {
+        Ok(args) => args,
+        Err(e) => {
+            eprintln!("{}\n{}", e, USAGE);
+            std::process::exit(2);
+        }
+    };
+
+    match args.target {
+        Target::Url(url) => {
+            let upstream: Box<dyn Transport> = if args.sse {
+                Box::new(LegacySseClientTransport::connect(&url)?)
+            } else {
+                Box::new(StreamableHttpClientTransport::new(&url)?)
+            };
+            bridge(Box::new(StdioTransport::new()), upstream)
+        }
+        Target::Command(program, command_args) => {
+            // Every HTTP session talks to its own child process
+            let run_session = move |transport: Box<dyn Transport>| {
+                let mut command = Command::new(&program);
+                command.args(&command_args);
+                let child = ChildStdioTransport::spawn(command)?;
+                bridge(transport, Box::new(child))
+            };
+            if args.sse {
+                let path = args.path.as_deref().unwrap_or("/sse");
+                sse_server::serve(&args.listen, path, "/messages", run_session)
+            } else {
+                let path = args.path.as_deref().unwrap_or("/mcp");
+                http_server::serve(&args.listen, path, run_session)
+            }
+        }
+    }
+}
+
+/// Relays messages between a downstream and an upstream transport until the
+/// upstream ends. Messages pass through untouched, so request ids,
+/// notifications and server-initiated requests all survive the hop.
+pub fn bridge(
+    downstream: Box<dyn Transport>,
+    upstream: Box<dyn Transport>,
+) -> Result<(), Box<dyn Error>> {
+    let (downstream_sender, downstream_receiver) = downstream.split();
+    let (upstream_sender, upstream_receiver) = upstream.split();
+
+    // When the downstream goes away, closing the upstream makes it wind down,
+    // which in turn ends the relay below
+    std::thread::spawn(move || pump(downstream_receiver, upstream_sender, "downstream"));
+    pump(upstream_receiver, downstream_sender, "upstream");
+    Ok(())
+}
+
+/// Forwards every message from `from` to `to`, then closes `to`.
+fn pump(mut from: Box<dyn TransportReceiver>, mut to: Box<dyn TransportSender>, side: &str) {
+    loop {
+        match from.receive() {
+            Ok(Some(message)) => {
+                if let Err(e) = to.send(&message) {
+                    eprintln!("Bridge: Failed to forward message from {}: {}", side, e);
+                    break;
+                }
+            }
+            Ok(None) => break,
+            Err(e) => {
+                eprintln!("Bridge: Error reading from {}: {}", side, e);
+                break;
+            }
+        }
+    }
+    if let Err(e) = to.close() {
+        eprintln!("Bridge: Error closing connection after {} ended: {}", side, e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lib::transport::MemoryTransport;
+
+    fn args(args: &[&str]) -> Result<BridgeArgs, String> {
+        parse_args(args.iter().map(|arg| arg.to_string()))
+    }
+
+    #[test]
+    fn messages_pass_through_untouched_in_both_directions() {
+        let (mut host, downstream) = MemoryTransport::pair();
+        let (upstream, mut server) = MemoryTransport::pair();
+        let relay =
+            std::thread::spawn(move || bridge(Box::new(downstream), Box::new(upstream)).is_ok());
+
+        // Ids the proxy would never pick, and spacing it would never write
+        let request = r#"{"jsonrpc":"2.0", "id":"host-7","method":"ping"}"#;
+        host.send(request).unwrap();
+        assert_eq!(server.receive().unwrap().as_deref(), Some(request));
+
+        let sampling = r#"{"jsonrpc":"2.0","id":0,"method":"sampling/createMessage","params":{}}"#;
+        let response = r#"{"jsonrpc":"2.0","result":{},"id":"host-7"}"#;
+        server.send(sampling).unwrap();
+        server.send(response).unwrap();
+        assert_eq!(host.receive().unwrap().as_deref(), Some(sampling));
+        assert_eq!(host.receive().unwrap().as_deref(), Some(response));
+
+        // The host leaving closes the upstream, whose end then ends the relay
+        host.close().unwrap();
+        assert_eq!(server.receive().unwrap(), None);
+        server.close().unwrap();
+        assert!(relay.join().unwrap());
+        assert_eq!(host.receive().unwrap(), None);
+    }
+
+    #[test]
+    fn a_url_bridges_to_stdio_and_a_command_to_http() {
+        let parsed = args(&["--sse", "http://127.0.0.1:8000/sse"]).unwrap();
+        assert!(parsed.sse);
+        assert!(matches!(parsed.target, Target::Url(url) if url == "http://127.0.0.1:8000/sse"));
+
+        let parsed = args(&["--listen", "0.0.0.0:9000", "--", "hello", "--verbose"]).unwrap();
+        assert_eq!(parsed.listen, "0.0.0.0:9000");
+        assert!(matches!(
+            parsed.target,
+            Target::Command(program, command_args) if program == "hello" && command_args == ["--verbose"]
+        ));
+    }
+
+    #[test]
+    fn bridges_need_exactly_one_target() {
+        assert!(args(&[]).is_err());
+        assert!(args(&["http://127.0.0.1:8000/mcp", "extra"]).is_err());
+        assert!(args(&["--bogus"]).is_err());
+    }
+}
@@ -0,0 +1,597 @@
+use serde::Deserialize;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use lib::transport::Framing;
+use lib::types::Tool;
+
+use crate::namespace::{CollisionPolicy, DEFAULT_SEPARATOR, ListedTool};
+
+/// Top-level proxy configuration, loaded from a TOML or JSON file.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    #[serde(default)]
+    pub downstream: DownstreamConfig,
+    #[serde(default = "default_collision")]
+    pub collision: CollisionPolicy,
+    #[serde(default = "default_separator")]
+    pub separator: String,
+    pub upstreams: Vec<UpstreamConfig>,
+    /// File the config was loaded from, named in errors found after loading.
+    #[serde(skip)]
+    pub source: Option<PathBuf>,
+}
+
+/// How the proxy itself is served to the downstream host.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DownstreamConfig {
+    #[serde(default)]
+    pub transport: DownstreamTransport,
+    /// Stdio framing; detected from the first message when unset.
+    pub framing: Option<Framing>,
+    /// Address to listen on for HTTP transports; `127.0.0.1:8000` when unset.
+    pub listen: Option<String>,
+    /// Endpoint path for HTTP transports; the SSE stream path for `sse`.
+    /// Defaults to `/mcp` for `streamable-http` and `/sse` for `sse`.
+    pub path: Option<String>,
+    /// Path clients POST messages to under the `sse` transport; `/messages`
+    /// when unset.
+    pub message_path: Option<String>,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum DownstreamTransport {
+    #[default]
+    Stdio,
+    StreamableHttp,
+    /// The legacy HTTP+SSE transport from protocol version 2024-11-05.
+    Sse,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum UpstreamTransport {
+    #[default]
+    Stdio,
+    StreamableHttp,
+    /// The legacy HTTP+SSE transport from protocol version 2024-11-05.
+    Sse,
+}
+
+/// A single upstream server.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct UpstreamConfig {
+    pub name: String,
+    #[serde(default)]
+    pub transport: UpstreamTransport,
+    pub command: Option<String>,
+    /// Endpoint URL for HTTP transports.
+    pub url: Option<String>,
+    #[serde(default)]
+    pub args: Vec<String>,
+    #[serde(default)]
+    pub env: HashMap<String, String>,
+    pub cwd: Option<PathBuf>,
+    /// Stdio framing; newline-delimited JSON unless set to `content-length`.
+    pub framing: Option<Framing>,
+    /// When set, every tool from this upstream is exposed as `<prefix><separator><tool>`.
+    pub prefix: Option<String>,
+    #[serde(default)]
+    pub tools: ToolsConfig,
+}
+
+/// Per-upstream tool filtering and overrides, all keyed by the upstream's tool names.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ToolsConfig {
+    /// If present, only these tools are exposed.
+    pub allow: Option<Vec<String>>,
+    /// Tools that are never exposed.
+    #[serde(default)]
+    pub deny: Vec<String>,
+    /// Maps an upstream tool name to the name exposed downstream (before prefixing).
+    #[serde(default)]
+    pub rename: HashMap<String, String>,
+    /// Maps an upstream tool name to a replacement description.
+    #[serde(default)]
+    pub descriptions: HashMap<String, String>,
+}
+
+fn default_collision() -> CollisionPolicy {
+    CollisionPolicy::PrefixOnConflict
+}
+
+fn default_separator() -> String {
+    DEFAULT_SEPARATOR.to_string()
+}
+
+impl<'de> Deserialize<'de> for CollisionPolicy {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let value = String::deserialize(deserializer)?;
+        value.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+impl Config {
+    /// Loads and validates a config file. The format is chosen from the file
+    /// extension: `.json` is parsed as JSON, anything else as TOML.
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| format!("{}: failed to read config: {}", path.display(), e))?;
+
+        let is_json = path.extension().and_then(|e| e.to_str()) == Some("json");
+        let parsed = if is_json {
+            let mut deserializer = serde_json::Deserializer::from_str(&text);
+            serde_path_to_error::deserialize(&mut deserializer)
+                .map_err(|e| format_path_error(e.path().to_string(), e.inner()))
+        } else {
+            let deserializer = toml::Deserializer::parse(&text)
+                .map_err(|e| format!("{}: {}", path.display(), e))?;
+            serde_path_to_error::deserialize(deserializer)
+                .map_err(|e| format_path_error(e.path().to_string(), e.inner()))
+        };
+
+        let mut config: Config = parsed.map_err(|e| format!("{}: {}", path.display(), e))?;
+        config.source = Some(path.to_path_buf());
+        config
+            .validate()
+            .map_err(|e| format!("{}: {}", path.display(), e))?;
+        Ok(config)
+    }
+
+    /// Checks constraints that cannot be expressed in the schema. Errors name the
+    /// offending key, e.g. `upstreams[1].command`.
+    pub fn validate(&self) -> Result<(), String> {
+        if self.upstreams.is_empty() {
+            return Err("upstreams: at least one upstream is required".to_string());
+        }
+        if self.separator.is_empty() {
+            return Err("separator: must not be empty".to_string());
+        }
+        if let Some(path) = &self.downstream.path
+            && !path.starts_with('/')
+        {
+            return Err("downstream.path: must start with '/'".to_string());
+        }
+        if let Some(path) = &self.downstream.message_path
+            && !path.starts_with('/')
+        {
+            return Err("downstream.message_path: must start with '/'".to_string());
+        }
+        let downstream = &self.downstream;
+        let stdio = downstream.transport == DownstreamTransport::Stdio;
+        let sse = downstream.transport == DownstreamTransport::Sse;
+        let settings = [
+            ("framing", downstream.framing.is_some(), stdio, "the 'stdio' transport"),
+            ("listen", downstream.listen.is_some(), !stdio, "HTTP transports"),
+            ("path", downstream.path.is_some(), !stdio, "HTTP transports"),
+            ("message_path", downstream.message_path.is_some(), sse, "the 'sse' transport"),
+        ];
+        for (setting, set, applies, applies_to) in settings {
+            if set && !applies {
+                return Err(format!("downstream.{}: only applies to {}", setting, applies_to));
+            }
+        }
+
+        let mut names = HashSet::new();
+        for (index, upstream) in self.upstreams.iter().enumerate() {
+            let key = format!("upstreams[{}]", index);
+            if upstream.name.is_empty() {
+                return Err(format!("{}.name: must not be empty", key));
+            }
+            if !names.insert(upstream.name.as_str()) {
+                return Err(format!(
+                    "{}.name: upstream '{}' is defined more than once",
+                    key, upstream.name
+                ));
+            }
+            if upstream.prefix.as_deref() == Some("") {
+                return Err(format!("{}.prefix: must not be empty", key));
+            }
+
+            match upstream.transport {
+                UpstreamTransport::Stdio => {
+                    match upstream.command.as_deref() {
+                        None => {
+                            return Err(format!(
+                                "{}.command: required for the 'stdio' transport",
+                                key
+                            ));
+                        }
+                        Some("") => return Err(format!("{}.command: must not be empty", key)),
+                        Some(_) => {}
+                    }
+                    if upstream.url.is_some() {
+                        return Err(format!("{}.url: only applies to HTTP transports", key));
+                    }
+                }
+                UpstreamTransport::StreamableHttp | UpstreamTransport::Sse => {
+                    match upstream.url.as_deref() {
+                        None => {
+                            return Err(format!("{}.url: required for HTTP transports", key));
+                        }
+                        Some(url)
+                            if !url.starts_with("http://") && !url.starts_with("https://") =>
+                        {
+                            return Err(format!("{}.url: '{}' is not an http(s) URL", key, url));
+                        }
+                        Some(_) => {}
+                    }
+                    // These only shape how a stdio upstream is spawned
+                    let stdio_only = [
+                        ("command", upstream.command.is_some()),
+                        ("args", !upstream.args.is_empty()),
+                        ("env", !upstream.env.is_empty()),
+                        ("cwd", upstream.cwd.is_some()),
+                        ("framing", upstream.framing.is_some()),
+                    ];
+                    if let Some((setting, _)) = stdio_only.iter().find(|(_, set)| *set) {
+                        return Err(format!(
+                            "{}.{}: only applies to the 'stdio' transport",
+                            key, setting
+                        ));
+                    }
+                }
+            }
+
+            if let Some(cwd) = &upstream.cwd
+                && !cwd.is_dir()
+            {
+                return Err(format!(
+                    "{}.cwd: '{}' is not a directory",
+                    key,
+                    cwd.display()
+                ));
+            }
+
+            let tools = &upstream.tools;
+            for name in &tools.deny {
+                if tools.allow.as_ref().is_some_and(|allow| allow.contains(name)) {
+                    return Err(format!(
+                        "{}.tools.deny: '{}' is also listed in tools.allow",
+                        key, name
+                    ));
+                }
+            }
+            let mut renamed = HashSet::new();
+            for (from, to) in &tools.rename {
+                if to.is_empty() {
+                    return Err(format!("{}.tools.rename.{}: must not be empty", key, from));
+                }
+                if !renamed.insert(to.as_str()) {
+                    return Err(format!(
+                        "{}.tools.rename.{}: '{}' is the target of more than one rename",
+                        key, from, to
+                    ));
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Checks the tool settings of the upstream at `index` against the tools it
+    /// lists, rejecting names in any of them that match no tool.
+    pub fn validate_tools(&self, index: usize, tools: &[Tool]) -> Result<(), String> {
+        let key = match &self.source {
+            Some(path) => format!("{}: upstreams[{}].tools", path.display(), index),
+            None => format!("upstreams[{}].tools", index),
+        };
+        let upstream = &self.upstreams[index];
+        let settings = &upstream.tools;
+
+        let mut named: Vec<(String, &String)> = Vec::new();
+        for name in settings.allow.iter().flatten() {
+            named.push((format!("{}.allow", key), name));
+        }
+        for name in &settings.deny {
+            named.push((format!("{}.deny", key), name));
+        }
+        let mut renamed: Vec<&String> = settings.rename.keys().collect();
+        renamed.sort();
+        for name in renamed {
+            named.push((format!("{}.rename.{}", key, name), name));
+        }
+        let mut described: Vec<&String> = settings.descriptions.keys().collect();
+        described.sort();
+        for name in described {
+            named.push((format!("{}.descriptions.{}", key, name), name));
+        }
+
+        for (key, name) in named {
+            if !tools.iter().any(|tool| &tool.name == name) {
+                return Err(format!(
+                    "{}: upstream '{}' lists no tool named '{}'",
+                    key, upstream.name, name
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl DownstreamConfig {
+    /// The endpoint path, or the default for the configured transport.
+    pub fn path(&self) -> &str {
+        match (&self.path, self.transport) {
+            (Some(path), _) => path,
+            (None, DownstreamTransport::Sse) => "/sse",
+            (None, _) => "/mcp",
+        }
+    }
+
+    /// The address to listen on for HTTP transports.
+    pub fn listen(&self) -> &str {
+        self.listen.as_deref().unwrap_or("127.0.0.1:8000")
+    }
+
+    /// The path clients POST messages to under the `sse` transport.
+    pub fn message_path(&self) -> &str {
+        self.message_path.as_deref().unwrap_or("/messages")
+    }
+}
+
+impl UpstreamConfig {
+    /// Builds the command used to spawn a stdio upstream.
+    pub fn command(&self) -> Option<Command> {
+        let mut command = Command::new(self.command.as_ref()?);
+        command.args(&self.args).envs(&self.env);
+        if let Some(cwd) = &self.cwd {
+            command.current_dir(cwd);
+        }
+        Some(command)
+    }
+}
+
+impl ToolsConfig {
+    /// Returns true if the upstream tool with this name should be exposed.
+    pub fn is_exposed(&self, name: &str) -> bool {
+        let allowed = self
+            .allow
+            .as_ref()
+            .is_none_or(|allow| allow.iter().any(|n| n == name));
+        allowed && !self.deny.iter().any(|n| n == name)
+    }
+
+    /// Filters an upstream's tools and applies renames and description overrides.
+    pub fn apply(&self, tools: Vec<Tool>) -> Vec<ListedTool> {
+        tools
+            .into_iter()
+            .filter(|tool| self.is_exposed(&tool.name))
+            .map(|mut tool| {
+                let upstream_tool_name = tool.name.clone();
+                if let Some(name) = self.rename.get(&upstream_tool_name) {
+                    tool.name = name.clone();
+                }
+                if let Some(description) = self.descriptions.get(&upstream_tool_name) {
+                    tool.description = description.clone();
+                }
+                ListedTool {
+                    tool,
+                    upstream_tool_name,
+                }
+            })
+            .collect()
+    }
+}
+
+fn format_path_error(path: String, error: &dyn std::fmt::Display) -> String {
+    if path == "." {
+        error.to_string()
+    } else {
+        format!("{}: {}", path, error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn parse(text: &str) -> Config {
+        toml::from_str(text).unwrap()
+    }
+
+    fn tool(name: &str) -> Tool {
+        Tool {
+            name: name.to_string(),
+            description: String::new(),
+            input_schema: json!({ "type": "object" }),
+        }
+    }
+
+    #[test]
+    fn stdio_settings_are_rejected_on_http_upstreams() {
+        let config = parse(
+            r#"
+            [[upstreams]]
+            name = "local"
+            command = "hello"
+
+            [[upstreams]]
+            name = "remote"
+            transport = "streamable-http"
+            url = "http://127.0.0.1:8000/mcp"
+            args = ["--verbose"]
+            "#,
+        );
+        assert_eq!(
+            config.validate().unwrap_err(),
+            "upstreams[1].args: only applies to the 'stdio' transport"
+        );
+
+        let config = parse(
+            r#"
+            [[upstreams]]
+            name = "remote"
+            transport = "sse"
+            url = "http://127.0.0.1:8000/sse"
+            command = "hello"
+            "#,
+        );
+        assert_eq!(
+            config.validate().unwrap_err(),
+            "upstreams[0].command: only applies to the 'stdio' transport"
+        );
+    }
+
+    #[test]
+    fn urls_are_rejected_on_stdio_upstreams() {
+        let config = parse(
+            r#"
+            [[upstreams]]
+            name = "local"
+            command = "hello"
+            url = "http://127.0.0.1:8000/mcp"
+            "#,
+        );
+        assert_eq!(
+            config.validate().unwrap_err(),
+            "upstreams[0].url: only applies to HTTP transports"
+        );
+    }
+
+    #[test]
+    fn tool_settings_must_match_listed_tools() {
+        let config = parse(
+            r#"
+            [[upstreams]]
+            name = "local"
+            command = "hello"
+
+            [upstreams.tools]
+            allow = ["greet", "sleep"]
+            rename = { greet = "hello" }
+            "#,
+        );
+        config.validate().unwrap();
+        config.validate_tools(0, &[tool("greet"), tool("sleep")]).unwrap();
+        assert_eq!(
+            config.validate_tools(0, &[tool("greet")]).unwrap_err(),
+            "upstreams[0].tools.allow: upstream 'local' lists no tool named 'sleep'"
+        );
+
+        let config = parse(
+            r#"
+            [[upstreams]]
+            name = "local"
+            command = "hello"
+
+            [upstreams.tools]
+            rename = { great = "hello" }
+            "#,
+        );
+        assert_eq!(
+            config.validate_tools(0, &[tool("greet")]).unwrap_err(),
+            "upstreams[0].tools.rename.great: upstream 'local' lists no tool named 'great'"
+        );
+    }
+
+    #[test]
+    fn framing_is_rejected_on_http_upstreams() {
+        let config = parse(
+            r#"
+            [[upstreams]]
+            name = "remote"
+            transport = "streamable-http"
+            url = "http://127.0.0.1:8000/mcp"
+            framing = "content-length"
+            "#,
+        );
+        assert_eq!(
+            config.validate().unwrap_err(),
+            "upstreams[0].framing: only applies to the 'stdio' transport"
+        );
+    }
+
+    #[test]
+    fn deny_and_description_keys_must_match_listed_tools() {
+        let config = parse(
+            r#"
+            [[upstreams]]
+            name = "local"
+            command = "hello"
+
+            [upstreams.tools]
+            deny = ["sleeep"]
+            "#,
+        );
+        assert_eq!(
+            config.validate_tools(0, &[tool("sleep")]).unwrap_err(),
+            "upstreams[0].tools.deny: upstream 'local' lists no tool named 'sleeep'"
+        );
+
+        let config = parse(
+            r#"
+            [[upstreams]]
+            name = "local"
+            command = "hello"
+
+            [upstreams.tools]
+            descriptions = { greeet = "Says hello" }
+            "#,
+        );
+        assert_eq!(
+            config.validate_tools(0, &[tool("greet")]).unwrap_err(),
+            "upstreams[0].tools.descriptions.greeet: upstream 'local' lists no tool named 'greeet'"
+        );
+    }
+
+    #[test]
+    fn http_settings_are_rejected_on_a_stdio_downstream() {
+        let config = parse(
+            r#"
+            [downstream]
+            listen = "127.0.0.1:9000"
+
+            [[upstreams]]
+            name = "local"
+            command = "hello"
+            "#,
+        );
+        assert_eq!(
+            config.validate().unwrap_err(),
+            "downstream.listen: only applies to HTTP transports"
+        );
+    }
+
+    #[test]
+    fn framing_is_rejected_on_an_http_downstream() {
+        let config = parse(
+            r#"
+            [downstream]
+            transport = "streamable-http"
+            framing = "content-length"
+
+            [[upstreams]]
+            name = "local"
+            command = "hello"
+            "#,
+        );
+        assert_eq!(
+            config.validate().unwrap_err(),
+            "downstream.framing: only applies to the 'stdio' transport"
+        );
+
+        let config = parse(
+            r#"
+            [downstream]
+            transport = "streamable-http"
+            message_path = "/messages"
+
+            [[upstreams]]
+            name = "local"
+            command = "hello"
+            "#,
+        );
+        assert_eq!(
+            config.validate().unwrap_err(),
+            "downstream.message_path: only applies to the 'sse' transport"
+        );
+    }
+}
@@ -0,0 +1,280 @@
+mod bridge;
+mod config;
+mod namespace;
+mod upstream;
+
+use config::{Config, DownstreamConfig, DownstreamTransport, UpstreamConfig};
+use lib::server::Server;
+use lib::transport::{StdioTransport, Transport, http_server, sse_server};
+use namespace::{CollisionPolicy, DEFAULT_SEPARATOR, UpstreamTools};
+use std::error::Error;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use upstream::{ProxyTool, Upstream};
+
+const USAGE: &str = "Usage: proxy --config <file.toml|file.json>
+       proxy [--collision <error|first-wins|prefix-on-conflict>] [--separator <sep>] [<prefix>=]<upstream-command> ...
+       proxy bridge ...";
+
+/// Builds the configuration from the command line, either by loading `--config`
+/// or from upstreams given inline as `[<prefix>=]<command>`.
+fn parse_args() -> Result<Config, String> {
+    let mut config_path: Option<PathBuf> = None;
+    let mut collision = CollisionPolicy::PrefixOnConflict;
+    let mut separator = DEFAULT_SEPARATOR.to_string();
+    let mut upstreams: Vec<UpstreamConfig> = Vec::new();
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--config" => {
+                config_path = Some(args.next().ok_or("--config requires a value")?.into());
+            }
+            "--collision" => {
+                let value = args.next().ok_or("--collision requires a value")?;
+                collision = value.parse()?;
+            }
+            "--separator" => {
+                separator = args.next().ok_or("--separator requires a value")?;
+            }
+            _ => {
+                let (prefix, command) = match arg.split_once('=') {
+                    Some((prefix, command)) => (Some(prefix.to_string()), command.to_string()),
+                    None => (None, arg),
+                };
+                let mut name = prefix.clone().unwrap_or_else(|| {
+                    Path::new(&command)
+                        .file_stem()
+                        .and_then(|s| s.to_str())
+                        .unwrap_or(&command)
+                        .to_string()
+                });
+                // Upstream names are used as conflict prefixes, so keep them unique
+                if upstreams.iter().any(|u| u.name == name) {
+                    if prefix.is_some() {
+                        return Err(format!("Prefix '{}' is used by more than one upstream", name));
+                    }
+                    name = format!("{}{}", name, upstreams.len() + 1);
+                }
+                upstreams.push(UpstreamConfig {
+                    name,
+                    transport: Default::default(),
+                    command: Some(command),
+                    url: None,
+                    args: Vec::new(),
+                    env: Default::default(),
+                    cwd: None,
+                    framing: None,
+                    prefix,
+                    tools: Default::default(),
+                });
+            }
+        }
+    }
+
+    if let Some(path) = config_path {
+        if !upstreams.is_empty() {
+            return Err("Upstream commands cannot be combined with --config".to_string());
+        }
+        return Config::load(&path);
+    }
+
+    let config = Config {
+        downstream: DownstreamConfig::default(),
+        collision,
+        separator,
+        upstreams,
+        source: None,
+    };
+    config.validate()?;
+    Ok(config)
+}
+
+/// Builds a downstream server presenting the proxy's identity and the merged tools.
+fn build_server(transport: Box<dyn Transport>, instructions: &str, tools: &[ProxyTool]) -> Server {
+    let mut builder = Server::builder("proxy", env!("CARGO_PKG_VERSION"))
+        .instructions(instructions)
+        .transport(transport);
+    for tool in tools {
+        builder = builder.async_tool(Arc::new(tool.clone()));
+    }
+    builder.build()
+}
+
+/// Lists every upstream's tools and merges them into the tools the proxy exposes.
+async fn proxy_tools(config: &Config, upstreams: &[Upstream]) -> Result<Vec<ProxyTool>, Box<dyn Error>> {
+    let mut listed = Vec::new();
+    for (index, upstream) in upstreams.iter().enumerate() {
+        let tools = upstream.list_tools().await?;
+        config.validate_tools(index, &tools)?;
+        listed.push(UpstreamTools {
+            name: &upstream.name,
+            prefix: upstream.prefix.as_deref(),
+            tools: config.upstreams[index].tools.apply(tools),
+        });
+    }
+
+    // Merge tools/list results; each tool routes back to the upstream that listed it
+    let routes = namespace::resolve(&listed, config.collision, &config.separator)?;
+
+    let tools = routes
+        .into_iter()
+        .map(|route| {
+            let upstream = &upstreams[route.upstream];
+            ProxyTool {
+                tool: route.tool,
+                upstream_name: upstream.name.clone(),
+                upstream_tool_name: route.upstream_tool_name,
+                client: upstream.client.clone(),
+            }
+        })
+        .collect();
+    Ok(tools)
+}
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let mut args = std::env::args().skip(1).peekable();
+    if args.next_if_eq("bridge").is_some() {
+        return bridge::run(args);
+    }
+
+    let config = match parse_args() {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("{}\n{}", e, USAGE);
+            std::process::exit(2);
+        }
+    };
+
+    // Upstream clients work from any runtime; this one drives setup and stdio serving
+    let runtime = tokio::runtime::Runtime::new()?;
+
+    // Connect to every upstream before serving so the merged tool list is complete
+    let mut upstreams = Vec::new();
+    for upstream_config in &config.upstreams {
+        upstreams.push(runtime.block_on(Upstream::connect(upstream_config))?);
+    }
+
+    let tools = runtime.block_on(proxy_tools(&config, &upstreams))?;
+
+    let names: Vec<&str> = upstreams.iter().map(|u| u.name.as_str()).collect();
+    let instructions = format!(
+        "Proxies tools from these MCP servers: {}. Calls are forwarded to the server that \
+         provides the tool.",
+        names.join(", ")
+    );
+
+    let downstream = &config.downstream;
+    match downstream.transport {
+        DownstreamTransport::Stdio => {
+            let mut transport = StdioTransport::new();
+            if let Some(framing) = downstream.framing {
+                transport = transport.with_framing(framing);
+            }
+            let server = build_server(Box::new(transport), &instructions, &tools);
+            runtime.block_on(server.serve())?;
+        }
+        DownstreamTransport::StreamableHttp => {
+            // Each HTTP session gets its own Server; upstream connections are shared
+            http_server::serve(downstream.listen(), downstream.path(), move |transport| {
+                build_server(transport, &instructions, &tools).run()
+            })?;
+        }
+        DownstreamTransport::Sse => {
+            sse_server::serve(
+                downstream.listen(),
+                downstream.path(),
+                downstream.message_path(),
+                move |transport| {
+                    build_server(transport, &instructions, &tools).run()
+                },
+            )?;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lib::client::Client;
+    use lib::transport::MemoryTransport;
+    use lib::types::{
+        Tool, ToolContext, ToolImplementation, ToolsCallResult, success_content, text_content,
+    };
+    use serde_json::json;
+
+    /// Answers with the upstream and tool that handled the call.
+    struct Answer {
+        upstream: &'static str,
+        name: &'static str,
+    }
+
+    impl ToolImplementation for Answer {
+        fn get_tool(&self) -> Tool {
+            Tool {
+                name: self.name.to_string(),
+                description: String::new(),
+                input_schema: json!({ "type": "object" }),
+            }
+        }
+
+        fn call(&self, _arguments: serde_json::Value, _context: ToolContext) -> ToolsCallResult {
+            success_content(vec![text_content(format!("{}:{}", self.upstream, self.name))])
+        }
+    }
+
+    /// Serves an in-process upstream named `name` with `tools` and connects to it.
+    async fn upstream(name: &'static str, tools: &[&'static str]) -> Upstream {
+        let (client_end, server_end) = MemoryTransport::pair();
+        let mut builder = Server::builder(name, "1.0").transport(Box::new(server_end));
+        for &tool in tools {
+            builder = builder.tool(Box::new(Answer { upstream: name, name: tool }));
+        }
+        tokio::spawn(builder.build().serve());
+        Upstream {
+            name: name.to_string(),
+            prefix: None,
+            client: Arc::new(Client::connect(Box::new(client_end)).await.unwrap()),
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn calls_are_routed_to_the_upstream_that_listed_the_tool() {
+        let config: Config = toml::from_str(
+            r#"
+            [[upstreams]]
+            name = "a"
+            command = "a"
+
+            [[upstreams]]
+            name = "b"
+            command = "b"
+            "#,
+        )
+        .unwrap();
+        let upstreams = [
+            upstream("a", &["greet", "add"]).await,
+            upstream("b", &["greet"]).await,
+        ];
+        let tools = proxy_tools(&config, &upstreams).await.unwrap();
+
+        let (client_end, server_end) = MemoryTransport::pair();
+        tokio::spawn(build_server(Box::new(server_end), "", &tools).serve());
+        let client = Client::connect(Box::new(client_end)).await.unwrap();
+
+        let listed = client.list_tools(None).await.unwrap();
+        let mut names: Vec<&str> = listed.tools.iter().map(|t| t.name.as_str()).collect();
+        names.sort();
+        assert_eq!(names, ["a__greet", "add", "b__greet"]);
+
+        for (name, answer) in [("a__greet", "a:greet"), ("add", "a:add"), ("b__greet", "b:greet")] {
+            let result = client.call_tool(name.to_string(), json!({})).await.unwrap();
+            assert_eq!(result.content[0].content["text"], answer);
+        }
+
+        let error = client.call_tool("greet".to_string(), json!({})).await.unwrap_err();
+        assert_eq!(error.code(), Some(-32602));
+    }
+}
@@ -0,0 +1,228 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::str::FromStr;
+
+use lib::types::Tool;
+
+/// Separator placed between an upstream prefix and the upstream's tool name.
+pub const DEFAULT_SEPARATOR: &str = "__";
+
+/// What to do when two upstreams expose a tool under the same name.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CollisionPolicy {
+    /// Refuse to start.
+    Error,
+    /// Keep the tool from the upstream listed first and drop the others.
+    FirstWins,
+    /// Prefix every conflicting tool with its upstream's prefix, or with the
+    /// upstream's name when it has none.
+    PrefixOnConflict,
+}
+
+impl FromStr for CollisionPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "error" => Ok(CollisionPolicy::Error),
+            "first-wins" => Ok(CollisionPolicy::FirstWins),
+            "prefix-on-conflict" => Ok(CollisionPolicy::PrefixOnConflict),
+            _ => Err(format!(
+                "Unknown collision policy '{}' (expected 'error', 'first-wins' or 'prefix-on-conflict')",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for CollisionPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CollisionPolicy::Error => write!(f, "error"),
+            CollisionPolicy::FirstWins => write!(f, "first-wins"),
+            CollisionPolicy::PrefixOnConflict => write!(f, "prefix-on-conflict"),
+        }
+    }
+}
+
+/// A tool listed by an upstream, after per-upstream renames and overrides.
+pub struct ListedTool {
+    /// Tool definition carrying the (possibly renamed) unprefixed name.
+    pub tool: Tool,
+    /// Name of the tool as the upstream knows it.
+    pub upstream_tool_name: String,
+}
+
+/// The tools listed by one upstream, along with how they should be namespaced.
+pub struct UpstreamTools<'a> {
+    pub name: &'a str,
+    /// When set, every tool from this upstream is exposed as `<prefix><separator><tool>`.
+    pub prefix: Option<&'a str>,
+    pub tools: Vec<ListedTool>,
+}
+
+/// A tool as exposed downstream, and where calls to it must be forwarded.
+pub struct Route {
+    /// Tool definition carrying the downstream-facing name.
+    pub tool: Tool,
+    /// Index of the owning upstream in the slice passed to [`resolve`].
+    pub upstream: usize,
+    /// Name of the tool as the upstream knows it.
+    pub upstream_tool_name: String,
+}
+
+/// Computes downstream tool names for every upstream tool, applying prefixes and
+/// the collision policy. Routes are returned in upstream order.
+pub fn resolve(
+    upstreams: &[UpstreamTools],
+    policy: CollisionPolicy,
+    separator: &str,
+) -> Result<Vec<Route>, String> {
+    // Candidate names after applying the configured per-upstream prefixes
+    let mut candidates = Vec::new();
+    for (index, upstream) in upstreams.iter().enumerate() {
+        let mut seen = HashSet::new();
+        for listed in &upstream.tools {
+            let tool = &listed.tool;
+            if !seen.insert(tool.name.as_str()) {
+                eprintln!(
+                    "Proxy: Upstream '{}' lists tool '{}' more than once. Using the first.",
+                    upstream.name, tool.name
+                );
+                continue;
+            }
+            let name = match upstream.prefix {
+                Some(prefix) => format!("{}{}{}", prefix, separator, tool.name),
+                None => tool.name.clone(),
+            };
+            candidates.push((name, index, listed));
+        }
+    }
+
+    let mut owners: HashMap<&str, Vec<usize>> = HashMap::new();
+    for (name, index, _) in &candidates {
+        owners.entry(name.as_str()).or_default().push(*index);
+    }
+
+    let mut routes = Vec::new();
+    let mut exposed = HashSet::new();
+    for (name, index, listed) in &candidates {
+        let owning = &owners[name.as_str()];
+        let exposed_name = if owning.len() == 1 {
+            name.clone()
+        } else {
+            match policy {
+                CollisionPolicy::Error => {
+                    let names: Vec<String> = owning
+                        .iter()
+                        .map(|&i| format!("'{}'", upstreams[i].name))
+                        .collect();
+                    return Err(format!(
+                        "Tool '{}' is provided by multiple upstreams: {}",
+                        name,
+                        names.join(", ")
+                    ));
+                }
+                CollisionPolicy::FirstWins => {
+                    if owning[0] != *index {
+                        eprintln!(
+                            "Proxy: Tool '{}' from upstream '{}' is already provided by upstream '{}'. Skipping.",
+                            name, upstreams[*index].name, upstreams[owning[0]].name
+                        );
+                        continue;
+                    }
+                    name.clone()
+                }
+                CollisionPolicy::PrefixOnConflict => {
+                    // Tools of a prefixed upstream already carry their prefix
+                    let upstream = &upstreams[*index];
+                    let prefix = upstream.prefix.unwrap_or(upstream.name);
+                    format!("{}{}{}", prefix, separator, listed.tool.name)
+                }
+            }
+        };
+
+        // Prefixing can itself produce a name another upstream already uses
+        if !exposed.insert(exposed_name.clone()) {
+            return Err(format!(
+                "Tool '{}' from upstream '{}' is exposed as '{}', which is already taken",
+                listed.tool.name, upstreams[*index].name, exposed_name
+            ));
+        }
+
+        let mut exposed_tool = listed.tool.clone();
+        exposed_tool.name = exposed_name;
+        routes.push(Route {
+            tool: exposed_tool,
+            upstream: *index,
+            upstream_tool_name: listed.upstream_tool_name.clone(),
+        });
+    }
+
+    Ok(routes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn upstream<'a>(name: &'a str, prefix: Option<&'a str>, tools: &[&str]) -> UpstreamTools<'a> {
+        let tools = tools
+            .iter()
+            .map(|&tool| ListedTool {
+                tool: Tool {
+                    name: tool.to_string(),
+                    description: String::new(),
+                    input_schema: json!({ "type": "object" }),
+                },
+                upstream_tool_name: tool.to_string(),
+            })
+            .collect();
+        UpstreamTools { name, prefix, tools }
+    }
+
+    fn exposed(routes: &[Route]) -> Vec<(&str, usize)> {
+        routes.iter().map(|r| (r.tool.name.as_str(), r.upstream)).collect()
+    }
+
+    #[test]
+    fn conflicts_are_prefixed_with_the_upstream_name_without_a_prefix() {
+        let upstreams = [
+            upstream("github", None, &["search", "issues"]),
+            upstream("gitlab", None, &["search"]),
+        ];
+        let routes = resolve(&upstreams, CollisionPolicy::PrefixOnConflict, "__").unwrap();
+        assert_eq!(
+            exposed(&routes),
+            [("github__search", 0), ("issues", 0), ("gitlab__search", 1)]
+        );
+    }
+
+    #[test]
+    fn conflicts_use_the_configured_prefix() {
+        // `gh` exposes `gh__search`, which the second upstream also lists as is
+        let upstreams = [
+            upstream("github", Some("gh"), &["search"]),
+            upstream("mirror", None, &["gh__search"]),
+        ];
+        let routes = resolve(&upstreams, CollisionPolicy::PrefixOnConflict, "__").unwrap();
+        assert_eq!(
+            exposed(&routes),
+            [("gh__search", 0), ("mirror__gh__search", 1)]
+        );
+        assert_eq!(routes[0].upstream_tool_name, "search");
+    }
+
+    #[test]
+    fn upstreams_sharing_a_prefix_cannot_both_expose_a_tool() {
+        let upstreams = [
+            upstream("github", Some("git"), &["search"]),
+            upstream("gitlab", Some("git"), &["search"]),
+        ];
+        let Err(error) = resolve(&upstreams, CollisionPolicy::PrefixOnConflict, "__") else {
+            panic!("expected a collision error");
+        };
+        assert!(error.contains("git__search"), "{}", error);
+    }
+}
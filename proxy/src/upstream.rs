@@ -0,0 +1,123 @@
+use async_trait::async_trait;
+use std::sync::Arc;
+
+use lib::Error;
+use lib::client::Client;
+use lib::jsonrpc::JsonRpcError;
+use lib::transport::ChildStdioTransport;
+
+use crate::config::{UpstreamConfig, UpstreamTransport};
+use lib::types::{AsyncToolImplementation, Tool, ToolContext, ToolsCallResult, error_content};
+
+/// A connection to a single upstream MCP server.
+pub struct Upstream {
+    pub name: String,
+    pub prefix: Option<String>,
+    pub client: Arc<Client>,
+}
+
+impl Upstream {
+    /// Spawns the upstream server and performs the initialization handshake.
+    pub async fn connect(config: &UpstreamConfig) -> Result<Self, Box<dyn std::error::Error>> {
+        let client = match config.transport {
+            UpstreamTransport::Stdio => {
+                let command = config.command().ok_or("Missing upstream command")?;
+                eprintln!(
+                    "Proxy: Connecting to upstream '{}' ({:?})...",
+                    config.name, command
+                );
+                let transport = ChildStdioTransport::spawn(command)?.with_framing(config.framing.unwrap_or_default());
+                Client::connect(Box::new(transport)).await?
+            }
+            UpstreamTransport::StreamableHttp => {
+                let url = config.url.as_deref().ok_or("Missing upstream URL")?;
+                eprintln!("Proxy: Connecting to upstream '{}' ({})...", config.name, url);
+                Client::connect_http(url).await?
+            }
+            UpstreamTransport::Sse => {
+                let url = config.url.as_deref().ok_or("Missing upstream URL")?;
+                eprintln!("Proxy: Connecting to upstream '{}' ({})...", config.name, url);
+                Client::connect_sse(url).await?
+            }
+        };
+        Ok(Upstream {
+            name: config.name.clone(),
+            prefix: config.prefix.clone(),
+            client: Arc::new(client),
+        })
+    }
+
+    /// Fetches every tool the upstream exposes, following pagination cursors.
+    pub async fn list_tools(&self) -> Result<Vec<Tool>, Box<dyn std::error::Error>> {
+        let mut tools = Vec::new();
+        if !self.client.supports("tools/list") {
+            eprintln!("Proxy: Upstream '{}' offers no tools", self.name);
+            return Ok(tools);
+        }
+        let mut cursor = None;
+        loop {
+            let result = self.client.list_tools(cursor).await?;
+            tools.extend(result.tools);
+            match result.next_cursor {
+                Some(next) => cursor = Some(next),
+                None => break,
+            }
+        }
+        Ok(tools)
+    }
+}
+
+/// A tool that forwards calls to the upstream server that owns it. Calls to the
+/// same upstream share one connection and may be in flight together.
+#[derive(Clone)]
+pub struct ProxyTool {
+    /// Tool definition as exposed downstream, possibly under a prefixed name.
+    pub tool: Tool,
+    pub upstream_name: String,
+    /// Name of the tool on the upstream, used when forwarding calls.
+    pub upstream_tool_name: String,
+    pub client: Arc<Client>,
+}
+
+#[async_trait]
+impl AsyncToolImplementation for ProxyTool {
+    fn get_tool(&self) -> Tool {
+        self.tool.clone()
+    }
+
+    async fn call(&self, arguments: serde_json::Value, context: ToolContext) -> ToolsCallResult {
+        self.try_call(arguments, context)
+            .await
+            .unwrap_or_else(|error| error_content(error.message))
+    }
+
+    /// Errors the upstream answered with reach the client with the same code,
+    /// message and data. Other failures become an error result. If the client
+    /// cancels, this future is dropped, which cancels the upstream request under
+    /// the id the upstream knows it by. Progress the upstream reports is relayed
+    /// under the client's own token.
+    async fn try_call(
+        &self,
+        arguments: serde_json::Value,
+        context: ToolContext,
+    ) -> Result<ToolsCallResult, JsonRpcError> {
+        let name = self.upstream_tool_name.clone();
+        let result = if context.progress_token().is_some() {
+            self.client
+                .call_tool_with_progress(name, arguments, move |progress| {
+                    context.report_progress(progress.progress, progress.total, progress.message)
+                })
+                .await
+        } else {
+            self.client.call_tool(name, arguments).await
+        };
+        match result {
+            Ok(result) => Ok(result),
+            Err(Error::Protocol(error)) => Err(error),
+            Err(e) => Ok(error_content(format!(
+                "Upstream '{}' failed to call '{}': {}",
+                self.upstream_name, self.upstream_tool_name, e
+            ))),
+        }
+    }
+}
@@ -1,11 +1,24 @@
+use async_trait::async_trait;
 use lib::server::Server;
+use lib::transport::Transport;
 use lib::types::{
-    Tool, ToolImplementation, ToolsCallResult, error_content, success_content, text_content,
+    AsyncToolImplementation, Prompt, PromptArgument, PromptImplementation, PromptMessage,
+    Resource, ResourceContents, ResourceNotifier, ResourceProvider, ResourceTemplate, Role, Tool,
+    ToolContext, ToolImplementation, ToolsCallResult, error_content, prompt_message, success_content,
+    text_content, text_resource,
 };
 use serde_json::json;
+use std::collections::HashMap;
 use std::error::Error;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
 
-struct GreetTool;
+/// Counts greetings, which are also readable as the `hello://greetings` resource.
+struct GreetTool {
+    greetings: Arc<AtomicU64>,
+    notifier: ResourceNotifier,
+}
 
 impl ToolImplementation for GreetTool {
     fn get_tool(&self) -> Tool {
@@ -25,10 +38,13 @@ impl ToolImplementation for GreetTool {
         }
     }
 
-    fn call(&self, arguments: serde_json::Value) -> ToolsCallResult {
+    fn call(&self, arguments: serde_json::Value, context: ToolContext) -> ToolsCallResult {
         match arguments.get("name").and_then(|v| v.as_str()) {
             Some(name) => {
                 let greeting = format!("Hello, {}! Welcome to the MCP server.", name);
+                context.logger().info(&format!("Greeting {}", name));
+                self.greetings.fetch_add(1, Ordering::Relaxed);
+                self.notifier.notify_updated(GREETINGS_URI);
                 success_content(vec![text_content(greeting)])
             }
             None => error_content("Missing required parameter 'name'".to_string()),
@@ -51,7 +67,7 @@ impl ToolImplementation for GetTimeTool {
         }
     }
 
-    fn call(&self, _arguments: serde_json::Value) -> ToolsCallResult {
+    fn call(&self, _arguments: serde_json::Value, _context: ToolContext) -> ToolsCallResult {
         use std::time::{SystemTime, UNIX_EPOCH};
 
         match SystemTime::now().duration_since(UNIX_EPOCH) {
@@ -65,14 +81,193 @@ impl ToolImplementation for GetTimeTool {
     }
 }
 
+/// Waits before answering, without tying up the server while it does.
+struct SleepTool;
+
+#[async_trait]
+impl AsyncToolImplementation for SleepTool {
+    fn get_tool(&self) -> Tool {
+        Tool {
+            name: "sleep".to_string(),
+            description: "Wait for the given number of seconds, reporting progress each second, then respond"
+                .to_string(),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "seconds": {
+                        "type": "number",
+                        "description": "How long to wait, at most 60 seconds"
+                    }
+                },
+                "required": ["seconds"]
+            }),
+        }
+    }
+
+    async fn call(&self, arguments: serde_json::Value, context: ToolContext) -> ToolsCallResult {
+        match arguments.get("seconds").and_then(|v| v.as_f64()) {
+            Some(seconds) if (0.0..=60.0).contains(&seconds) => {
+                context.logger().debug(&format!("Sleeping for {} seconds", seconds));
+                let done = tokio::time::sleep(Duration::from_secs_f64(seconds));
+                tokio::pin!(done);
+                // Reports progress once a second, starting right away
+                let mut ticks = tokio::time::interval(Duration::from_secs(1));
+                let mut elapsed = 0;
+                loop {
+                    tokio::select! {
+                        _ = &mut done => {
+                            break success_content(vec![text_content(format!("Slept for {} seconds", seconds))]);
+                        }
+                        _ = ticks.tick() => {
+                            let message = format!("Slept {} of {} seconds", elapsed, seconds);
+                            context.report_progress(elapsed as f64, Some(seconds), Some(message));
+                            elapsed += 1;
+                        }
+                        _ = context.cancelled() => {
+                            context.logger().debug("Sleep was cancelled");
+                            break error_content("Sleep was cancelled".to_string());
+                        }
+                    }
+                }
+            }
+            Some(_) => error_content("'seconds' must be between 0 and 60".to_string()),
+            None => error_content("Missing required parameter 'seconds'".to_string()),
+        }
+    }
+}
+
+/// Exposes a fixed description of the server, plus a greeting for any name.
+struct HelloResources {
+    greetings: Arc<AtomicU64>,
+}
+
+const GREETINGS_URI: &str = "hello://greetings";
+
+#[async_trait]
+impl ResourceProvider for HelloResources {
+    async fn list(&self) -> Vec<Resource> {
+        vec![
+            Resource {
+                uri: "hello://about".to_string(),
+                name: "About".to_string(),
+                description: Some("What this server does".to_string()),
+                mime_type: Some("text/plain".to_string()),
+            },
+            Resource {
+                uri: GREETINGS_URI.to_string(),
+                name: "Greetings".to_string(),
+                description: Some("How many greetings the greet tool has given".to_string()),
+                mime_type: Some("text/plain".to_string()),
+            },
+        ]
+    }
+
+    fn templates(&self) -> Vec<ResourceTemplate> {
+        vec![ResourceTemplate {
+            uri_template: "greeting://{name}".to_string(),
+            name: "Greeting".to_string(),
+            description: Some("A greeting for the given name".to_string()),
+            mime_type: Some("text/plain".to_string()),
+        }]
+    }
+
+    async fn read(
+        &self,
+        uri: &str,
+        variables: HashMap<String, String>,
+    ) -> Result<Vec<ResourceContents>, String> {
+        let text = match variables.get("name") {
+            Some(name) => format!("Hello, {}!", name),
+            None if uri == GREETINGS_URI => {
+                format!("{} greetings so far", self.greetings.load(Ordering::Relaxed))
+            }
+            None => "An example MCP server with a few tools and resources.".to_string(),
+        };
+        Ok(vec![text_resource(uri, Some("text/plain"), text)])
+    }
+}
+
+/// Asks the model to write a welcome message for someone.
+struct WelcomePrompt;
+
+impl PromptImplementation for WelcomePrompt {
+    fn get_prompt(&self) -> Prompt {
+        Prompt {
+            name: "welcome".to_string(),
+            description: Some("Write a welcome message for a new team member".to_string()),
+            arguments: vec![
+                PromptArgument {
+                    name: "name".to_string(),
+                    description: Some("Who to welcome".to_string()),
+                    required: true,
+                },
+                PromptArgument {
+                    name: "tone".to_string(),
+                    description: Some("How the message should sound, e.g. formal".to_string()),
+                    required: false,
+                },
+            ],
+        }
+    }
+
+    fn render(&self, arguments: HashMap<String, String>) -> Result<Vec<PromptMessage>, String> {
+        let name = &arguments["name"];
+        let tone = arguments.get("tone").map(String::as_str).unwrap_or("friendly");
+        Ok(vec![prompt_message(
+            Role::User,
+            format!("Write a short, {} welcome message for {}.", tone, name),
+        )])
+    }
+}
+
+/// Configures a hello server, serving over stdio unless a transport is given.
+fn build_server(transport: Option<Box<dyn Transport>>) -> Server {
+    let greetings = Arc::new(AtomicU64::new(0));
+    let notifier = ResourceNotifier::new();
+
+    let mut builder = Server::builder("hello", env!("CARGO_PKG_VERSION"))
+        .instructions(
+            "Greets people and tells the time. Read hello://greetings to see how many \
+             greetings were given, or use the welcome prompt to draft a welcome message.",
+        )
+        .resource_notifier(notifier.clone())
+        // Tools
+        .tool(Box::new(GreetTool {
+            greetings: greetings.clone(),
+            notifier,
+        }))
+        .tool(Box::new(GetTimeTool))
+        .async_tool(Arc::new(SleepTool))
+        // Resources
+        .resource_provider(Arc::new(HelloResources { greetings }))
+        // Prompts
+        .prompt(Box::new(WelcomePrompt));
+    if let Some(transport) = transport {
+        builder = builder.transport(transport);
+    }
+    builder.build()
+}
+
 fn main() -> Result<(), Box<dyn Error>> {
-    let mut server = Server::new();
+    let args: Vec<String> = std::env::args().skip(1).collect();
 
-    // Register tools
-    server.register_tool(Box::new(GreetTool));
-    server.register_tool(Box::new(GetTimeTool));
+    // `--http <addr>` serves Streamable HTTP at http://<addr>/mcp instead of stdio
+    if let Some(position) = args.iter().position(|a| a == "--http") {
+        let addr = args.get(position + 1).ok_or("--http requires an address")?;
+        return lib::transport::http_server::serve(addr, "/mcp", move |transport| {
+            build_server(Some(transport)).run()
+        });
+    }
+
+    // `--sse <addr>` serves the legacy HTTP+SSE transport at http://<addr>/sse
+    if let Some(position) = args.iter().position(|a| a == "--sse") {
+        let addr = args.get(position + 1).ok_or("--sse requires an address")?;
+        return lib::transport::sse_server::serve(addr, "/sse", "/messages", move |transport| {
+            build_server(Some(transport)).run()
+        });
+    }
 
-    server.run()?;
+    build_server(None).run()?;
 
     Ok(())
 }